@@ -0,0 +1,22 @@
+#!/bin/sh
+# 构建 crontab-ui-runner 并放到 src-tauri/binaries，由 Tauri 作为 externalBin 随主程序打包
+# 用法: sh scripts/build-runner.sh [release|debug]
+set -e
+
+PROFILE="${1:-release}"
+cd "$(dirname "$0")/../src-tauri"
+
+TRIPLE=$(rustc -vV | sed -n 's/^host: //p')
+TARGET="binaries/crontab-ui-runner-$TRIPLE"
+
+# tauri-build 在编译 crate 时就要求 externalBin 存在，先放一个占位文件
+mkdir -p binaries
+[ -f "$TARGET" ] || touch "$TARGET"
+
+if [ "$PROFILE" = "release" ]; then
+    cargo build --release --bin crontab-ui-runner
+else
+    cargo build --bin crontab-ui-runner
+fi
+
+cp "target/$PROFILE/crontab-ui-runner" "$TARGET"
@@ -0,0 +1,232 @@
+//! 本地 HTTP/JSON 接口，供内部看板与脚本查询任务状态
+//!
+//! 默认关闭。开启后只监听 `127.0.0.1`，每个请求都需要带上 `Authorization: Bearer <token>`。
+//! 接口直接调用对应的 Tauri 命令，行为与界面操作一致：
+//!
+//! - `GET /api/jobs?tag=<tag>`：任务列表
+//! - `GET /api/jobs/<id>/history`：任务的执行历史
+//! - `POST /api/history`：按条件查询执行历史，请求体与 `query_job_history` 的参数相同
+//! - `POST /api/logs/search`：全文搜索执行输出，请求体与 `search_logs` 的参数相同
+//! - `POST /api/jobs/<id>/run`：立即执行任务
+//! - `POST /api/jobs/<id>/toggle`：启用或停用任务，请求体为 `{"is_active": true}`
+
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::block_on;
+use tiny_http::{Header, Method, Request, Response, Server};
+use uuid::Uuid;
+use crontab_ui::db::{HistoryQuery, LogSearchQuery};
+use crontab_ui::Error;
+
+/// 接口设置在 `app_settings` 中的键
+pub const SETTINGS_KEY: &str = "api_server";
+
+/// 默认监听端口
+pub const DEFAULT_PORT: u16 = 8765;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiServerSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String,
+}
+
+impl Default for ApiServerSettings {
+    fn default() -> Self {
+        ApiServerSettings {
+            enabled: false,
+            port: DEFAULT_PORT,
+            token: generate_token(),
+        }
+    }
+}
+
+pub fn generate_token() -> String {
+    Uuid::new_v4().to_simple().to_string()
+}
+
+#[derive(Deserialize)]
+struct ToggleRequest {
+    is_active: bool,
+}
+
+/// 运行中的接口服务，停止时等待监听线程退出
+pub struct ApiServer {
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ApiServer {
+    pub fn start(settings: &ApiServerSettings) -> Result<Self, Error> {
+        if settings.token.is_empty() {
+            return Err(Error::ApiServerError("接口令牌不能为空".to_string()));
+        }
+
+        let server = Server::http((Ipv4Addr::LOCALHOST, settings.port))
+            .map_err(|e| Error::ApiServerError(format!("启动接口服务失败: {}", e)))?;
+        let server = Arc::new(server);
+
+        let token = settings.token.clone();
+        let listener = Arc::clone(&server);
+        let thread = std::thread::spawn(move || {
+            for request in listener.incoming_requests() {
+                let token = token.clone();
+                // 立即执行任务可能耗时较长，每个请求单独处理，避免阻塞其他请求
+                std::thread::spawn(move || handle(request, &token));
+            }
+        });
+
+        Ok(ApiServer { server, thread: Some(thread) })
+    }
+
+    pub fn stop(mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// 按字节比较令牌，耗时与不匹配的位置无关
+fn token_matches(request: &Request, token: &str) -> bool {
+    let Some(header) = request.headers().iter().find(|header| header.field.equiv("Authorization")) else {
+        return false;
+    };
+    let Some(provided) = header.value.as_str().strip_prefix("Bearer ") else {
+        return false;
+    };
+
+    provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn json_response(status: u16, body: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    let content_type = Header::from_bytes("Content-Type", "application/json; charset=utf-8")
+        .expect("valid header");
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type)
+}
+
+fn error_response(status: u16, message: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    json_response(status, serde_json::json!({ "error": message }).to_string())
+}
+
+fn status_for(error: &Error) -> u16 {
+    match error {
+        Error::JobNotFound => 404,
+        Error::InvalidCronExpression(_)
+        | Error::InvalidCommand(_)
+        | Error::InvalidDependency(_)
+        | Error::InvalidImport(_)
+        | Error::InvalidNotification(_)
+        | Error::InvalidSuccessRule(_) => 400,
+        _ => 500,
+    }
+}
+
+/// 解码 URL 中的 `%XX` 与 `+`
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| percent_decode(value))
+}
+
+fn parse_body<T: for<'de> Deserialize<'de>>(request: &mut Request) -> Result<T, (u16, String)> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(|e| (400, format!("读取请求体失败: {}", e)))?;
+    serde_json::from_str(&body).map_err(|e| (400, format!("无效的请求体: {}", e)))
+}
+
+fn to_json<T: Serialize>(result: Result<T, Error>) -> Result<String, (u16, String)> {
+    let value = result.map_err(|e| (status_for(&e), e.to_string()))?;
+    serde_json::to_string(&value).map_err(|e| (500, e.to_string()))
+}
+
+/// 按请求路径调用对应的命令
+fn route(request: &mut Request) -> Result<String, (u16, String)> {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<String> = path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+
+    match (request.method(), segments.as_slice()) {
+        (Method::Get, ["api", "jobs"]) => {
+            to_json(block_on(crate::get_cron_jobs(query_param(query, "tag"))))
+        }
+        (Method::Get, ["api", "jobs", id, "history"]) => {
+            to_json(block_on(crate::get_job_history(id.to_string())))
+        }
+        (Method::Post, ["api", "history"]) => {
+            let query: HistoryQuery = parse_body(request)?;
+            to_json(block_on(crate::query_job_history(query)))
+        }
+        (Method::Post, ["api", "logs", "search"]) => {
+            let query: LogSearchQuery = parse_body(request)?;
+            to_json(block_on(crate::search_logs(query)))
+        }
+        (Method::Post, ["api", "jobs", id, "run"]) => {
+            to_json(block_on(crate::run_cron_job(id.to_string())))
+        }
+        (Method::Post, ["api", "jobs", id, "toggle"]) => {
+            let body: ToggleRequest = parse_body(request)?;
+            to_json(block_on(crate::toggle_cron_job(id.to_string(), body.is_active)))
+        }
+        _ => Err((404, format!("未知的接口: {} {}", request.method(), path))),
+    }
+}
+
+fn handle(mut request: Request, token: &str) {
+    let response = if !token_matches(&request, token) {
+        error_response(401, "令牌无效")
+    } else {
+        match route(&mut request) {
+            Ok(body) => json_response(200, body),
+            Err((status, message)) => error_response(status, &message),
+        }
+    };
+
+    if let Err(e) = request.respond(response) {
+        eprintln!("Failed to send API response: {}", e);
+    }
+}
@@ -0,0 +1,450 @@
+//! 无界面的命令行工具：`crontab-ui-cli <command>`
+//!
+//! 与图形界面共用数据库与 crontab 同步逻辑，便于通过 SSH 管理任务。
+//! 所有命令都支持 `--json`，以 JSON 输出结果，出错时输出 `{"error": "..."}`。
+//! 修改任务后只重写 crontab，不重启 cron 服务，cron 会自动读取新的 crontab。
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::Duration as StdDuration;
+use chrono::{DateTime, Duration, Local, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use uuid::Uuid;
+use crontab_ui::crontab::{self, CrontabExportMode};
+use crontab_ui::db::{self, Database, LogSearchQuery};
+use crontab_ui::transfer::{self, ExportFormat};
+use crontab_ui::logs::{self, LogStore};
+use crontab_ui::{deps, runner, schedule, CronJob, Error, JobStatus, RunTrigger};
+
+#[derive(Parser)]
+#[command(name = "crontab-ui-cli", about = "命令行管理 crontab-ui 任务")]
+struct Cli {
+    /// 以 JSON 输出结果
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// 列出任务
+    List {
+        /// 只列出带有该标签的任务
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// 添加任务，默认停用
+    Add {
+        #[arg(long)]
+        schedule: String,
+        #[arg(long)]
+        command: String,
+        #[arg(long)]
+        name: Option<String>,
+        /// 可重复指定多个标签
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// 添加后立即启用
+        #[arg(long)]
+        enable: bool,
+        /// 由 cron 把该任务的输出发送到此地址
+        #[arg(long)]
+        mailto: Option<String>,
+    },
+    /// 修改任务，未指定的字段保持不变
+    Edit {
+        id: String,
+        #[arg(long)]
+        schedule: Option<String>,
+        #[arg(long)]
+        command: Option<String>,
+        #[arg(long)]
+        name: Option<String>,
+        /// 替换任务的全部标签，可重复指定
+        #[arg(long = "tag")]
+        tags: Option<Vec<String>>,
+        /// 由 cron 把该任务的输出发送到此地址，传入空字符串时清除
+        #[arg(long)]
+        mailto: Option<String>,
+    },
+    /// 启用任务
+    Enable {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// 停用任务
+    Disable {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// 删除任务
+    Delete {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// 立即执行任务，并按依赖触发下游任务
+    Run {
+        id: String,
+    },
+    /// 查看任务最近一次执行的日志
+    Logs {
+        id: String,
+        /// 持续输出该次执行追加的内容，直到执行结束或按 Ctrl-C 退出
+        #[arg(long, short)]
+        follow: bool,
+    },
+    /// 查看任务的执行历史
+    History {
+        id: String,
+        /// 最多显示的记录数
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// 在所有任务的执行输出中搜索，例如 `search "disk full" --days 7`
+    Search {
+        text: String,
+        /// 只搜索该任务
+        #[arg(long)]
+        job: Option<String>,
+        /// 只搜索最近若干天的执行
+        #[arg(long)]
+        days: Option<i64>,
+        #[arg(long, default_value_t = 20)]
+        limit: u32,
+    },
+    /// 计算任务接下来的执行时间
+    NextRuns {
+        id: String,
+        #[arg(long, default_value_t = 5)]
+        count: usize,
+    },
+    /// 导出任务
+    Export {
+        /// 只导出指定的任务，未指定时导出全部任务（crontab 格式导出全部启用中的任务）
+        ids: Vec<String>,
+        #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+        format: OutputFormat,
+        /// crontab 格式下与应用写入的条目相同，由本机的 runner 执行；导出的片段只能在这台机器上使用
+        #[arg(long)]
+        wrapped: bool,
+        /// 写入文件，未指定时输出到标准输出
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Json,
+    Yaml,
+    Toml,
+    Crontab,
+}
+
+#[derive(Serialize)]
+struct NextRuns {
+    job_id: String,
+    next_runs: Vec<DateTime<Local>>,
+}
+
+#[derive(Serialize)]
+struct JobLogs {
+    job_id: String,
+    logs: String,
+}
+
+#[derive(Serialize)]
+struct Changed {
+    changed: usize,
+}
+
+/// `logs --follow` 的轮询间隔
+const LOG_POLL_INTERVAL: StdDuration = StdDuration::from_millis(500);
+
+type CliResult = Result<(), Box<dyn std::error::Error>>;
+
+fn open_database() -> Result<Database, Box<dyn std::error::Error>> {
+    let data_dir = crontab_ui::data_dir().ok_or("无法获取应用数据目录")?;
+    Database::new(data_dir)
+}
+
+fn find_job(db: &Database, id: &str) -> Result<CronJob, Box<dyn std::error::Error>> {
+    Ok(db.get_job(id)?.ok_or(Error::JobNotFound)?)
+}
+
+/// 按数据库中的任务重写 crontab，`ids` 中的任务以数据库为准
+fn sync_crontab(db: &Database, ids: &[String]) -> CliResult {
+    let jobs = db.get_all_jobs()?;
+    let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+    crontab::reconcile(&jobs, &ids)?;
+    Ok(())
+}
+
+/// `--json` 时输出 JSON，否则输出文本
+fn print<T: Serialize>(json: bool, value: &T, text: impl FnOnce() -> String) -> CliResult {
+    if json {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        let text = text();
+        if !text.is_empty() {
+            println!("{}", text);
+        }
+    }
+    Ok(())
+}
+
+fn job_line(job: &CronJob) -> String {
+    format!(
+        "{}  {}  {:<15}  {}  {}",
+        job.id,
+        if job.is_active { "on " } else { "off" },
+        if job.is_scheduled() { job.schedule.as_str() } else { "(依赖触发)" },
+        job.name.as_deref().unwrap_or("-"),
+        job.command.lines().next().unwrap_or(""),
+    )
+}
+
+/// 先输出最近一次执行的日志末尾一段，之后每隔一段时间输出该次执行追加的内容，执行结束后退出；
+/// `--json` 时每段输出一行 JSON
+fn follow_log(json: bool, job_id: &str) -> CliResult {
+    let log_store = LogStore::default();
+    let mut chunk = log_store.read_tail(job_id, None, logs::DEFAULT_TAIL_BYTES)?;
+    loop {
+        if json {
+            println!("{}", serde_json::to_string(&chunk)?);
+        } else {
+            if chunk.reset {
+                println!("--- 日志已删除，改为查看最近一次执行 ---");
+            }
+            print!("{}", chunk.data);
+            std::io::stdout().flush()?;
+        }
+        if chunk.finished && chunk.next_offset >= chunk.size {
+            return Ok(());
+        }
+
+        loop {
+            std::thread::sleep(LOG_POLL_INTERVAL);
+            let next = log_store.read_from(job_id, chunk.run_id.as_deref(), chunk.next_offset, logs::MAX_CHUNK_BYTES)?;
+            if !next.data.is_empty() || next.reset || next.finished {
+                chunk = next;
+                break;
+            }
+        }
+    }
+}
+
+fn set_active(db: &Database, json: bool, ids: Vec<String>, is_active: bool) -> CliResult {
+    for id in &ids {
+        let job = find_job(db, id)?;
+        if is_active {
+            job.validate()?;
+        }
+    }
+    db.set_jobs_active(&ids, is_active)?;
+    sync_crontab(db, &ids)?;
+    print(json, &Changed { changed: ids.len() }, String::new)
+}
+
+fn run(cli: Cli) -> CliResult {
+    let db = open_database()?;
+    let json = cli.json;
+
+    match cli.command {
+        Commands::List { tag } => {
+            let jobs = match tag {
+                Some(tag) => db.get_jobs_by_tag(&tag)?,
+                None => db.get_all_jobs()?,
+            };
+            print(json, &jobs, || jobs.iter().map(job_line).collect::<Vec<_>>().join("\n"))
+        }
+        Commands::Add { schedule, command, name, tags, enable, mailto } => {
+            let job = CronJob {
+                id: Uuid::new_v4().to_string(),
+                name,
+                schedule,
+                command,
+                is_active: enable,
+                tags,
+                mailto,
+                ..CronJob::default()
+            };
+            job.validate()?;
+            db.add_job(&job)?;
+            if job.is_active {
+                sync_crontab(&db, std::slice::from_ref(&job.id))?;
+            }
+            print(json, &job, || job.id.clone())
+        }
+        Commands::Edit { id, schedule, command, name, tags, mailto } => {
+            let mut job = find_job(&db, &id)?;
+            if let Some(schedule) = schedule {
+                job.schedule = schedule;
+            }
+            if let Some(command) = command {
+                job.command = command;
+            }
+            if name.is_some() {
+                job.name = name;
+            }
+            if let Some(tags) = tags {
+                job.tags = tags;
+            }
+            if let Some(mailto) = mailto {
+                job.mailto = Some(mailto).filter(|mailto| !mailto.trim().is_empty());
+            }
+            job.validate()?;
+            deps::validate_dependencies(&db.get_all_jobs()?, &job)?;
+            db.update_job(&job)?;
+            sync_crontab(&db, std::slice::from_ref(&job.id))?;
+            print(json, &job, || job_line(&job))
+        }
+        Commands::Enable { ids } => set_active(&db, json, ids, true),
+        Commands::Disable { ids } => set_active(&db, json, ids, false),
+        Commands::Delete { ids } => {
+            let changed = db.delete_jobs(&ids)?;
+            sync_crontab(&db, &ids)?;
+            print(json, &Changed { changed }, String::new)
+        }
+        Commands::Run { id } => {
+            let job = find_job(&db, &id)?;
+            let histories = runner::run_chain(&db, &job, RunTrigger::Manual)?;
+            let exit_code = match histories.first() {
+                Some(history) if matches!(history.status, JobStatus::Failed) => history.exit_code.unwrap_or(1),
+                _ => 0,
+            };
+            print(json, &histories, || {
+                histories
+                    .iter()
+                    .map(|history| format!("{} {:?} ({:?})\n{}", history.job_id, history.status, history.trigger, history.output))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })?;
+            // 与 runner 一致，以任务的退出码退出
+            if exit_code != 0 {
+                std::process::exit(exit_code);
+            }
+            Ok(())
+        }
+        Commands::Logs { id, follow: false } => {
+            find_job(&db, &id)?;
+            let Some(mut reader) = LogStore::default().open(&id)? else {
+                return print(json, &JobLogs { job_id: id, logs: String::new() }, String::new);
+            };
+            if json {
+                let mut bytes = Vec::new();
+                reader.read_to_end(&mut bytes)?;
+                let result = JobLogs { job_id: id, logs: String::from_utf8_lossy(&bytes).to_string() };
+                print(json, &result, String::new)
+            } else {
+                // 大日志直接写到标准输出，不整体读入内存
+                std::io::copy(&mut reader, &mut std::io::stdout().lock())?;
+                Ok(())
+            }
+        }
+        Commands::Logs { id, follow: true } => {
+            find_job(&db, &id)?;
+            follow_log(json, &id)
+        }
+        Commands::History { id, limit } => {
+            let mut histories = db.get_job_history(&id)?;
+            histories.truncate(limit);
+            print(json, &histories, || {
+                histories
+                    .iter()
+                    .map(|history| format!(
+                        "{}  {:?}  exit={}  {}ms",
+                        history.execution_time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                        history.status,
+                        history.exit_code.map_or("-".to_string(), |code| code.to_string()),
+                        history.duration_ms.map_or("-".to_string(), |ms| ms.to_string()),
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+        Commands::Search { text, job, days, limit } => {
+            if text.trim().chars().count() < db::MIN_SEARCH_CHARS {
+                return Err(format!("搜索内容至少需要 {} 个字符", db::MIN_SEARCH_CHARS).into());
+            }
+            let query = LogSearchQuery {
+                text,
+                job_id: job,
+                from: days.map(|days| Utc::now() - Duration::days(days)),
+                to: None,
+                limit: Some(limit),
+                offset: None,
+            };
+            let page = db.search_logs(&query)?;
+            print(json, &page, || {
+                page.items
+                    .iter()
+                    .map(|hit| format!(
+                        "{}  {}  {:?}  {}\n    {}",
+                        hit.execution_time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                        hit.job_name.as_deref().unwrap_or(&hit.job_id),
+                        hit.status,
+                        hit.history_id,
+                        hit.snippet.trim_end().replace('\n', "\n    "),
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+        Commands::NextRuns { id, count } => {
+            let job = find_job(&db, &id)?;
+            let next_runs = schedule::next_fire_times(&job.schedule, count)?;
+            let result = NextRuns { job_id: id, next_runs };
+            print(json, &result, || {
+                result.next_runs
+                    .iter()
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+        Commands::Export { ids, format, wrapped, output } => {
+            let mut jobs = db.get_all_jobs()?;
+            if !ids.is_empty() {
+                let ids: HashSet<&String> = ids.iter().collect();
+                jobs.retain(|job| ids.contains(&job.id));
+            } else if matches!(format, OutputFormat::Crontab) {
+                jobs.retain(|job| job.is_active);
+            }
+
+            let content = match format {
+                OutputFormat::Json => transfer::export_jobs(&jobs, ExportFormat::Json)?,
+                OutputFormat::Yaml => transfer::export_jobs(&jobs, ExportFormat::Yaml)?,
+                OutputFormat::Toml => transfer::export_jobs(&jobs, ExportFormat::Toml)?,
+                OutputFormat::Crontab => {
+                    let mode = if wrapped { CrontabExportMode::Wrapped } else { CrontabExportMode::Clean };
+                    crontab::export_crontab(&jobs, mode)?
+                }
+            };
+
+            match output {
+                Some(path) => std::fs::write(&path, content)?,
+                None => print!("{}", content),
+            }
+            Ok(())
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let json = cli.json;
+
+    if let Err(e) = run(cli) {
+        if json {
+            println!("{}", serde_json::json!({ "error": e.to_string() }));
+        } else {
+            eprintln!("错误: {}", e);
+        }
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,81 @@
+//! 由 crontab 调用的任务执行器：`crontab-ui-runner <job_id> [--print-output]`
+//!
+//! 从数据库读取任务命令并执行，输出写入任务日志，执行结果记录到 `job_history`，
+//! 随后触发依赖该任务的下游任务。`--print-output` 时同时把任务输出打印到标准输出，由 cron 发送到 `MAILTO`。
+//! 执行结果的通知由 `crontab-ui-runner --notify <history_id>` 在单独的进程中发送，不阻塞任务执行。
+//! `crontab-ui-runner --catch-up` 只检查启用了补执行策略的任务并补执行错过的执行，由受管理区段中单独的一行定时调用。
+
+use crontab_ui::db::Database;
+use crontab_ui::{missed, notify, runner, Error, JobHistory, RunTrigger};
+
+fn open_database() -> Result<Database, Box<dyn std::error::Error>> {
+    let data_dir = crontab_ui::data_dir().ok_or("无法获取应用数据目录")?;
+    Database::new(data_dir)
+}
+
+fn run(job_id: &str) -> Result<JobHistory, Box<dyn std::error::Error>> {
+    let db = open_database()?;
+    let job = db.get_job(job_id)?.ok_or(Error::JobNotFound)?;
+    let mut histories = runner::run_chain(&db, &job, RunTrigger::Schedule)?;
+    Ok(histories.remove(0))
+}
+
+fn catch_up() -> Result<(), Box<dyn std::error::Error>> {
+    let db = open_database()?;
+    missed::run_catch_up_policies(&db)?;
+    Ok(())
+}
+
+fn notify(history_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let db = open_database()?;
+    notify::notify_history(&db, history_id)?;
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == runner::CATCH_UP_FLAG) {
+        if let Err(e) = catch_up() {
+            eprintln!("[{}] 补执行检查失败: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(index) = args.iter().position(|arg| arg == runner::NOTIFY_FLAG) {
+        let Some(history_id) = args.get(index + 1) else {
+            eprintln!("用法: {} {} <history_id>", runner::RUNNER_BIN_NAME, runner::NOTIFY_FLAG);
+            std::process::exit(2);
+        };
+        if let Err(e) = notify(history_id) {
+            eprintln!("[{}] 发送执行记录 {} 的通知失败: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), history_id, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let print_output = args.iter().any(|arg| arg == runner::PRINT_OUTPUT_FLAG);
+    let Some(job_id) = args.iter().find(|arg| !arg.starts_with("--")).cloned() else {
+        eprintln!(
+            "用法: {} <job_id> [{}] | {} {}",
+            runner::RUNNER_BIN_NAME,
+            runner::PRINT_OUTPUT_FLAG,
+            runner::RUNNER_BIN_NAME,
+            runner::CATCH_UP_FLAG
+        );
+        std::process::exit(2);
+    };
+
+    match run(&job_id) {
+        Ok(history) => {
+            if print_output {
+                print!("{}", history.output);
+            }
+            std::process::exit(history.exit_code.unwrap_or(1))
+        }
+        Err(e) => {
+            eprintln!("[{}] 执行任务 {} 失败: {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"), job_id, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,332 @@
+//! 采集任务的 stdout 与 stderr
+//!
+//! runner 逐行读取任务输出：完整输出按到达顺序写入任务日志，供实时查看；
+//! 保存到执行历史的输出受大小上限约束，超出时保留开头与结尾，中间以省略说明代替。
+//! 分开采集时 stdout 与 stderr 各保存一份，可以加上距开始执行的时间，便于对照两路输出的先后顺序。
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+/// 输出采集设置在 `app_settings` 中的键
+pub const SETTINGS_KEY: &str = "output_capture";
+
+/// 单行最多读取的字节数，更长的行拆成多行保存
+const MAX_LINE_BYTES: u64 = 64 * 1024;
+
+/// 命令退出后继续读取剩余输出的最长时间。命令启动的后台进程可能一直持有输出管道，
+/// 超时后不再等待，读取线程留在后台直到管道关闭
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CaptureSettings {
+    /// 分别保存 stdout 与 stderr；关闭时与原先的 `2>&1` 一样合并为一路
+    pub separate_streams: bool,
+    /// 保存的每行输出前加上距开始执行的时间，例如 `[+1.250s]`
+    pub timestamps: bool,
+    /// 每次执行保存到执行历史的最大输出字节数，超出时保留开头与结尾各一半；为 0 时不限制
+    pub max_output_bytes: u64,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        CaptureSettings {
+            separate_streams: false,
+            timestamps: false,
+            max_output_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// 输出来源
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    fn index(self) -> usize {
+        match self {
+            OutputStream::Stdout => 0,
+            OutputStream::Stderr => 1,
+        }
+    }
+}
+
+struct Line {
+    elapsed: Duration,
+    stream: OutputStream,
+    text: Vec<u8>,
+}
+
+/// 发送给采集线程的事件
+enum Event {
+    Line(Line),
+    Exited(std::io::Result<ExitStatus>),
+}
+
+/// 按字节上限保留开头与结尾的输出行。开头装满后，之后的行都进入结尾，结尾超出时丢弃最早的行
+struct CappedLines {
+    limit: u64,
+    head: Vec<Line>,
+    head_bytes: u64,
+    head_full: bool,
+    tail: VecDeque<Line>,
+    tail_bytes: u64,
+    /// 按来源统计的省略字节数
+    omitted_bytes: [u64; 2],
+}
+
+impl CappedLines {
+    fn new(limit: u64) -> Self {
+        CappedLines {
+            limit,
+            head: Vec::new(),
+            head_bytes: 0,
+            head_full: false,
+            tail: VecDeque::new(),
+            tail_bytes: 0,
+            omitted_bytes: [0; 2],
+        }
+    }
+
+    fn push(&mut self, mut line: Line) {
+        let len = line.text.len() as u64;
+        let head_limit = self.limit / 2;
+        if self.limit == 0 || (!self.head_full && self.head_bytes + len <= head_limit) {
+            self.head_bytes += len;
+            self.head.push(line);
+            return;
+        }
+        self.head_full = true;
+
+        // 单行超过结尾的上限时只保留该行的末尾
+        let tail_limit = self.limit - head_limit;
+        if len > tail_limit {
+            let cut = (len - tail_limit) as usize;
+            line.text.drain(..cut);
+            self.omitted_bytes[line.stream.index()] += cut as u64;
+        }
+        self.tail_bytes += line.text.len() as u64;
+        self.tail.push_back(line);
+
+        while self.tail_bytes > tail_limit {
+            let Some(dropped) = self.tail.pop_front() else {
+                break;
+            };
+            self.tail_bytes -= dropped.text.len() as u64;
+            self.omitted_bytes[dropped.stream.index()] += dropped.text.len() as u64;
+        }
+    }
+}
+
+/// 一次执行采集到的输出
+pub struct CapturedOutput {
+    lines: CappedLines,
+    timestamps: bool,
+}
+
+impl CapturedOutput {
+    /// 因超出上限没有保存的字节数
+    pub fn omitted_bytes(&self) -> u64 {
+        self.lines.omitted_bytes.iter().sum()
+    }
+
+    fn render(&self, stream: Option<OutputStream>, timestamps: bool) -> String {
+        let mut bytes = Vec::new();
+        let mut append = |line: &Line| {
+            if stream.is_some_and(|stream| stream != line.stream) {
+                return;
+            }
+            if timestamps {
+                bytes.extend_from_slice(format!("[+{:.3}s] ", line.elapsed.as_secs_f64()).as_bytes());
+            }
+            bytes.extend_from_slice(&line.text);
+        };
+
+        let omitted = match stream {
+            Some(stream) => self.lines.omitted_bytes[stream.index()],
+            None => self.omitted_bytes(),
+        };
+        self.lines.head.iter().for_each(&mut append);
+        if omitted > 0 {
+            append(&Line {
+                elapsed: self.lines.tail.front().map_or(Duration::ZERO, |line| line.elapsed),
+                stream: stream.unwrap_or(OutputStream::Stdout),
+                text: format!("… 省略 {} 字节输出 …\n", omitted).into_bytes(),
+            });
+        }
+        self.lines.tail.iter().for_each(&mut append);
+
+        String::from_utf8_lossy(&bytes).to_string()
+    }
+
+    /// 按到达顺序合并的输出，按设置加上时间
+    pub fn combined(&self) -> String {
+        self.render(None, self.timestamps)
+    }
+
+    /// 单独一路输出，按设置加上时间
+    pub fn stream(&self, stream: OutputStream) -> String {
+        self.render(Some(stream), self.timestamps)
+    }
+}
+
+/// 逐行读取输出并发送给采集线程，超长的行按 `MAX_LINE_BYTES` 拆开
+fn forward_lines(reader: impl Read, stream: OutputStream, started: Instant, sender: mpsc::Sender<Event>) {
+    let mut reader = BufReader::new(reader);
+    loop {
+        let mut text = Vec::new();
+        match (&mut reader).take(MAX_LINE_BYTES).read_until(b'\n', &mut text) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = Line { elapsed: started.elapsed(), stream, text };
+                if sender.send(Event::Line(line)).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// 执行命令并采集 stdout 与 stderr：完整输出写入 `log`，每行输出在截断前交给 `on_line`
+/// （用于按成功规则判断），返回保存到执行历史的部分。
+/// 命令退出后最多再等待 [`DRAIN_TIMEOUT`] 读取剩余输出，不等待仍持有输出管道的后台进程
+pub fn run(
+    mut command: Command,
+    mut log: File,
+    settings: &CaptureSettings,
+    mut on_line: impl FnMut(&[u8]),
+) -> std::io::Result<(ExitStatus, CapturedOutput)> {
+    let started = Instant::now();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let (sender, receiver) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        let sender = sender.clone();
+        std::thread::spawn(move || forward_lines(stdout, OutputStream::Stdout, started, sender));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        let sender = sender.clone();
+        std::thread::spawn(move || forward_lines(stderr, OutputStream::Stderr, started, sender));
+    }
+    std::thread::spawn(move || {
+        let _ = sender.send(Event::Exited(child.wait()));
+    });
+
+    let mut lines = CappedLines::new(settings.max_output_bytes);
+    let mut log_error = None;
+    let mut status = None;
+    let mut deadline: Option<Instant> = None;
+    loop {
+        let event = match deadline {
+            None => receiver.recv().ok(),
+            Some(deadline) => receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())).ok(),
+        };
+        match event {
+            Some(Event::Line(line)) => {
+                // 日志写入失败时继续读取输出，避免任务因管道写满而阻塞
+                if log_error.is_none() {
+                    log_error = log.write_all(&line.text).err();
+                }
+                on_line(&line.text);
+                lines.push(line);
+            }
+            Some(Event::Exited(result)) => {
+                status = Some(result?);
+                deadline = Some(Instant::now() + DRAIN_TIMEOUT);
+            }
+            // 输出管道都已关闭且命令已退出，或等待剩余输出超时
+            None => break,
+        }
+    }
+    if let Some(e) = log_error {
+        eprintln!("写入日志失败: {}", e);
+    }
+
+    let status = status.ok_or_else(|| std::io::Error::other("等待命令退出失败"))?;
+    Ok((status, CapturedOutput { lines, timestamps: settings.timestamps }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempDir;
+
+    fn line(stream: OutputStream, text: &str) -> Line {
+        Line { elapsed: Duration::ZERO, stream, text: text.as_bytes().to_vec() }
+    }
+
+    fn captured(limit: u64, lines: impl IntoIterator<Item = Line>) -> CapturedOutput {
+        let mut capped = CappedLines::new(limit);
+        lines.into_iter().for_each(|line| capped.push(line));
+        CapturedOutput { lines: capped, timestamps: false }
+    }
+
+    #[test]
+    fn capped_lines_keep_the_head_and_the_tail() {
+        let output = captured(20, (0..10).map(|i| line(OutputStream::Stdout, &format!("out{}\n", i))));
+        assert_eq!(output.omitted_bytes(), 30);
+        assert_eq!(output.combined(), "out0\nout1\n… 省略 30 字节输出 …\nout8\nout9\n");
+
+        let unlimited = captured(0, (0..10).map(|i| line(OutputStream::Stdout, &format!("out{}\n", i))));
+        assert_eq!(unlimited.omitted_bytes(), 0);
+        assert_eq!(unlimited.combined().lines().count(), 10);
+    }
+
+    #[test]
+    fn capped_lines_keep_the_end_of_a_long_line_and_count_each_stream() {
+        let output = captured(10, [
+            line(OutputStream::Stdout, "ok\n"),
+            line(OutputStream::Stderr, "0123456789\n"),
+            line(OutputStream::Stdout, "end\n"),
+        ]);
+        assert_eq!(output.lines.omitted_bytes, [0, 11]);
+        assert_eq!(output.stream(OutputStream::Stdout), "ok\nend\n");
+        assert_eq!(output.stream(OutputStream::Stderr), "… 省略 11 字节输出 …\n");
+        assert_eq!(output.combined(), "ok\n… 省略 11 字节输出 …\nend\n");
+
+        let output = captured(10, [line(OutputStream::Stdout, "ok\n"), line(OutputStream::Stdout, "0123456789\n")]);
+        assert_eq!(output.combined(), "ok\n… 省略 6 字节输出 …\n6789\n");
+    }
+
+    #[test]
+    fn every_line_reaches_the_callback_before_truncation() {
+        let dir = TempDir::new();
+        let log = File::create(dir.path().join("run.log")).unwrap();
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("for i in 1 2 3 4 5; do echo line $i; done; echo ERROR; echo done");
+        let settings = CaptureSettings { max_output_bytes: 14, ..CaptureSettings::default() };
+
+        let mut seen = Vec::new();
+        let (status, output) = run(command, log, &settings, |line| seen.push(String::from_utf8_lossy(line).to_string())).unwrap();
+        assert!(status.success());
+        assert_eq!(seen.len(), 7);
+        assert_eq!(seen[5], "ERROR\n");
+        assert!(!output.combined().contains("ERROR"));
+        assert_eq!(std::fs::read_to_string(dir.path().join("run.log")).unwrap(), seen.concat());
+    }
+
+    #[test]
+    fn background_children_do_not_keep_the_run_open() {
+        let dir = TempDir::new();
+        let log = File::create(dir.path().join("run.log")).unwrap();
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("echo started; sleep 30 & echo done");
+
+        let started = Instant::now();
+        let (status, output) = run(command, log, &CaptureSettings::default(), |_| {}).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(status.success());
+        assert_eq!(output.combined(), "started\ndone\n");
+    }
+}
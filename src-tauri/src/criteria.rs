@@ -0,0 +1,197 @@
+//! 按任务的成功规则判断执行结果
+//!
+//! 有些脚本出错时仍然以 0 退出，只在输出中打印 `ERROR`。任务可以设置允许的退出码、
+//! 出现即失败的正则与必须出现的正则，由 runner 在执行结束后依次判断：
+//!
+//! 1. 退出码不在允许的退出码中（未设置时只允许 0）时失败
+//! 2. 输出匹配任一失败规则时失败
+//! 3. 输出缺少任一必需规则时失败
+//!
+//! 正则在执行期间逐行匹配（[`OutputMatcher`]），`^`、`$` 匹配每一行的开头与结尾，不能跨行匹配。
+//! 超出保存上限而没有记录到执行历史的输出同样参与判断。
+
+use regex::{Regex, RegexBuilder};
+use crate::{Error, JobStatus, SuccessRule};
+
+/// 单条正则的最大编译大小，防止规则过于复杂拖慢 runner
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+fn compile(pattern: &str) -> Result<Regex, Error> {
+    RegexBuilder::new(pattern)
+        .multi_line(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| Error::InvalidSuccessRule(format!("无效的正则 {}: {}", pattern, e)))
+}
+
+/// 检查规则：正则可以编译且不为空
+pub fn validate_rules(rules: &[SuccessRule]) -> Result<(), Error> {
+    for rule in rules {
+        match rule {
+            SuccessRule::ExitCode(_) => {}
+            SuccessRule::FailurePattern(pattern) | SuccessRule::RequiredPattern(pattern) => {
+                if pattern.is_empty() {
+                    return Err(Error::InvalidSuccessRule("正则不能为空".to_string()));
+                }
+                compile(pattern)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 规则的文字说明，记录在执行历史中
+pub fn describe(rule: &SuccessRule) -> String {
+    match rule {
+        SuccessRule::ExitCode(code) => format!("允许的退出码 {}", code),
+        SuccessRule::FailurePattern(pattern) => format!("输出匹配失败规则 /{}/", pattern),
+        SuccessRule::RequiredPattern(pattern) => format!("输出缺少必需内容 /{}/", pattern),
+    }
+}
+
+/// 一条正则规则的匹配状态
+struct PatternState {
+    rule: SuccessRule,
+    fails_on_match: bool,
+    /// 保存时已检查过正则，这里仍可能遇到数据库被外部修改后的无效规则
+    regex: Result<Regex, String>,
+    matched: bool,
+}
+
+/// 在执行期间逐行匹配输出，结束后结合退出码判断执行结果。
+/// 每条正则只编译一次，已匹配的正则不再参与之后的行
+pub struct OutputMatcher {
+    /// 允许的退出码，为空时只允许 0
+    accepted: Vec<i32>,
+    patterns: Vec<PatternState>,
+}
+
+impl OutputMatcher {
+    pub fn new(rules: &[SuccessRule]) -> Self {
+        let mut accepted = Vec::new();
+        let mut patterns = Vec::new();
+        for rule in rules {
+            let (pattern, fails_on_match) = match rule {
+                SuccessRule::ExitCode(code) => {
+                    accepted.push(*code);
+                    continue;
+                }
+                SuccessRule::FailurePattern(pattern) => (pattern, true),
+                SuccessRule::RequiredPattern(pattern) => (pattern, false),
+            };
+            patterns.push(PatternState {
+                rule: rule.clone(),
+                fails_on_match,
+                regex: compile(pattern).map_err(|e| e.to_string()),
+                matched: false,
+            });
+        }
+        OutputMatcher { accepted, patterns }
+    }
+
+    /// 匹配一行输出
+    pub fn feed(&mut self, line: &str) {
+        for pattern in &mut self.patterns {
+            if !pattern.matched {
+                pattern.matched = pattern.regex.as_ref().is_ok_and(|regex| regex.is_match(line));
+            }
+        }
+    }
+
+    /// 按规则判断执行结果，返回状态与决定结果的规则说明。
+    /// 没有退出码（命令无法启动或被信号终止）时直接判为失败；按默认规则判断时规则说明为空
+    pub fn finish(&self, exit_code: Option<i32>) -> (JobStatus, Option<String>) {
+        let Some(code) = exit_code else {
+            return (JobStatus::Failed, None);
+        };
+
+        if self.accepted.is_empty() {
+            if code != 0 {
+                return (JobStatus::Failed, None);
+            }
+        } else if !self.accepted.contains(&code) {
+            let accepted = self.accepted.iter().map(i32::to_string).collect::<Vec<_>>().join(", ");
+            return (JobStatus::Failed, Some(format!("退出码 {} 不在允许的退出码 {} 中", code, accepted)));
+        }
+
+        for pattern in &self.patterns {
+            if let Err(e) = &pattern.regex {
+                return (JobStatus::Failed, Some(e.clone()));
+            }
+            if pattern.matched == pattern.fails_on_match {
+                return (JobStatus::Failed, Some(describe(&pattern.rule)));
+            }
+        }
+
+        // 非 0 退出码按规则视为成功时记录对应的规则
+        let matched = (code != 0).then(|| describe(&SuccessRule::ExitCode(code)));
+        (JobStatus::Success, matched)
+    }
+}
+
+/// 按规则判断一段完整输出的执行结果，见 [`OutputMatcher::finish`]
+pub fn evaluate(rules: &[SuccessRule], exit_code: Option<i32>, output: &str) -> (JobStatus, Option<String>) {
+    let mut matcher = OutputMatcher::new(rules);
+    output.split_inclusive('\n').for_each(|line| matcher.feed(line));
+    matcher.finish(exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(pattern: &str) -> SuccessRule {
+        SuccessRule::FailurePattern(pattern.to_string())
+    }
+
+    fn required(pattern: &str) -> SuccessRule {
+        SuccessRule::RequiredPattern(pattern.to_string())
+    }
+
+    #[test]
+    fn exit_codes_decide_first() {
+        assert!(matches!(evaluate(&[], Some(0), ""), (JobStatus::Success, None)));
+        assert!(matches!(evaluate(&[], Some(1), ""), (JobStatus::Failed, None)));
+        assert!(matches!(evaluate(&[], None, ""), (JobStatus::Failed, None)));
+
+        let rules = [SuccessRule::ExitCode(0), SuccessRule::ExitCode(3)];
+        let (status, rule) = evaluate(&rules, Some(3), "");
+        assert!(matches!(status, JobStatus::Success));
+        assert_eq!(rule.as_deref(), Some("允许的退出码 3"));
+        let (status, rule) = evaluate(&rules, Some(1), "");
+        assert!(matches!(status, JobStatus::Failed));
+        assert_eq!(rule.as_deref(), Some("退出码 1 不在允许的退出码 0, 3 中"));
+    }
+
+    #[test]
+    fn patterns_match_line_by_line() {
+        let rules = [failure("^ERROR"), required("done$")];
+        assert!(matches!(evaluate(&rules, Some(0), "start\ndone\n"), (JobStatus::Success, None)));
+
+        let (status, rule) = evaluate(&rules, Some(0), "start\nERROR: disk full\ndone\n");
+        assert!(matches!(status, JobStatus::Failed));
+        assert_eq!(rule.as_deref(), Some("输出匹配失败规则 /^ERROR/"));
+
+        // `^` 只匹配行首
+        assert!(matches!(evaluate(&rules, Some(0), "no ERROR here\ndone\n").0, JobStatus::Success));
+
+        let (status, rule) = evaluate(&rules, Some(0), "start\n");
+        assert!(matches!(status, JobStatus::Failed));
+        assert_eq!(rule.as_deref(), Some("输出缺少必需内容 /done$/"));
+    }
+
+    #[test]
+    fn matcher_keeps_matches_across_lines_and_reports_invalid_rules() {
+        let rules = [failure("ERROR")];
+        let mut matcher = OutputMatcher::new(&rules);
+        matcher.feed("ERROR\n");
+        for _ in 0..1000 {
+            matcher.feed("ok\n");
+        }
+        assert!(matches!(matcher.finish(Some(0)).0, JobStatus::Failed));
+
+        let (status, rule) = evaluate(&[failure("(")], Some(0), "");
+        assert!(matches!(status, JobStatus::Failed));
+        assert!(rule.unwrap().contains("无效的正则"));
+    }
+}
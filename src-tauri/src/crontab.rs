@@ -0,0 +1,731 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use serde::{Deserialize, Serialize};
+use crate::{drift, runner, CronJob, Error};
+
+/// 受管理区段的起止标记，区段内的内容完全由数据库中的任务生成
+pub const SECTION_BEGIN: &str = "# BEGIN CRONTAB-UI MANAGED JOBS";
+pub const SECTION_END: &str = "# END CRONTAB-UI MANAGED JOBS";
+
+/// crontab 中解析出的一个受管理任务条目
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct CrontabEntry {
+    pub job_id: String,
+    pub name: Option<String>,
+    pub schedule: String,
+    /// 执行计划之后的整段命令
+    pub command_line: String,
+    /// 任务块中单独设置的 `MAILTO`
+    pub mailto: Option<String>,
+}
+
+impl CrontabEntry {
+    /// 条目实际执行的命令。runner 调用中不包含命令本身，返回 `None`；
+    /// 旧格式的条目去掉末尾写入任务日志的重定向
+    pub fn command(&self) -> Option<String> {
+        if runner::is_runner_command(&self.command_line) {
+            return None;
+        }
+
+        let command = self.command_line
+            .rsplit_once(" > '")
+            .filter(|(_, redirect)| redirect.ends_with("' 2>&1"))
+            .map_or(self.command_line.as_str(), |(command, _)| command);
+        Some(command.trim().to_string())
+    }
+
+    /// 条目是否按数据库中的配置执行该任务
+    pub fn runs_job(&self, job: &CronJob) -> bool {
+        match self.command() {
+            Some(command) => command == job.command.trim(),
+            None => self.command_line.split_whitespace().any(|arg| arg == job.id),
+        }
+    }
+
+    fn render(&self) -> String {
+        render_block(&self.job_id, self.name.as_deref(), self.mailto.as_deref(), &self.schedule, &self.command_line)
+    }
+}
+
+/// crontab 中的一个全局环境变量，例如 `MAILTO`、`SHELL`、`PATH`、`CRON_TZ`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CrontabVariable {
+    pub name: String,
+    pub value: String,
+}
+
+impl CrontabVariable {
+    fn render(&self) -> String {
+        render_variable(&self.name, &self.value)
+    }
+}
+
+/// 值为空或首尾有空白时加引号，cron 读取时会去掉引号
+fn render_variable(name: &str, value: &str) -> String {
+    if value.is_empty() || value.trim() != value {
+        format!("{}=\"{}\"", name, value)
+    } else {
+        format!("{}={}", name, value)
+    }
+}
+
+/// 任务块：`# JOB_ID:`、`# NAME:` 标记，单独设置的 `MAILTO`，以及任务行
+fn render_block(job_id: &str, name: Option<&str>, mailto: Option<&str>, schedule: &str, command_line: &str) -> String {
+    let mut lines = vec![
+        format!("# JOB_ID:{}", job_id),
+        format!("# NAME:{}", name.unwrap_or("")),
+    ];
+    if let Some(mailto) = mailto {
+        lines.push(render_variable("MAILTO", mailto));
+    }
+    lines.push(format!("{} {}", schedule, command_line));
+    lines.join("\n")
+}
+
+/// 将一行 crontab 拆分为执行计划与命令，`@daily` 等简写只占一个字段
+fn split_schedule(line: &str) -> Option<(String, String)> {
+    let mut rest = line.trim();
+    let field_count = if rest.starts_with('@') { 1 } else { 5 };
+
+    let mut fields = Vec::with_capacity(field_count);
+    for _ in 0..field_count {
+        let (field, remainder) = rest.split_once(char::is_whitespace)?;
+        fields.push(field);
+        rest = remainder.trim_start();
+    }
+
+    if rest.is_empty() {
+        return None;
+    }
+    Some((fields.join(" "), rest.to_string()))
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// 解析环境变量设置行，例如 `MAILTO=admin@example.com`，返回去掉首尾空白与引号的变量名和值
+fn parse_variable(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.split_once('=')?;
+    let name = name.trim();
+    if !is_variable_name(name) {
+        return None;
+    }
+
+    let value = value.trim();
+    let unquoted = ['"', '\'']
+        .iter()
+        .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+        .unwrap_or(value);
+    Some((name.to_string(), unquoted.to_string()))
+}
+
+fn is_variable(line: &str) -> bool {
+    parse_variable(line).is_some()
+}
+
+/// 逐行解析 crontab。`include_untagged` 时同时返回没有 `# JOB_ID:` 标记的任务行，其 `job_id` 为空
+fn parse(content: &str, include_untagged: bool) -> Vec<CrontabEntry> {
+    let mut entries = Vec::new();
+    let mut current_job_id: Option<String> = None;
+    let mut current_job_name: Option<String> = None;
+    let mut current_mailto: Option<String> = None;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            current_job_id = None;
+            current_job_name = None;
+            current_mailto = None;
+            continue;
+        }
+
+        if let Some(job_id) = line.strip_prefix("# JOB_ID:") {
+            current_job_id = Some(job_id.trim().to_string());
+            current_job_name = None;
+            current_mailto = None;
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("# NAME:") {
+            current_job_name = Some(name.trim().to_string()).filter(|name| !name.is_empty());
+            continue;
+        }
+
+        if current_job_id.is_some() {
+            if let Some((name, value)) = parse_variable(trimmed) {
+                if name == "MAILTO" {
+                    current_mailto = Some(value);
+                    continue;
+                }
+            }
+        }
+
+        if trimmed.starts_with('#') || is_variable(trimmed) {
+            continue;
+        }
+
+        // 受管理区段中没有 `# JOB_ID:` 标记的 runner 调用是补执行检查，不是任务
+        let job_id = match current_job_id.take() {
+            Some(job_id) => job_id,
+            None if include_untagged && !runner::is_runner_command(trimmed) => String::new(),
+            None => continue,
+        };
+        if let Some((schedule, command_line)) = split_schedule(trimmed) {
+            entries.push(CrontabEntry {
+                job_id,
+                name: current_job_name.take(),
+                schedule,
+                command_line,
+                mailto: current_mailto.take(),
+            });
+        }
+    }
+
+    entries
+}
+
+/// 解析 crontab 中所有带 `# JOB_ID:` 标记的任务条目，包括受管理区段之外的旧格式条目
+pub fn parse_entries(content: &str) -> Vec<CrontabEntry> {
+    parse(content, false)
+}
+
+/// 解析 crontab 文件中的所有任务行，没有 `# JOB_ID:` 标记的任务行 `job_id` 为空
+pub fn parse_all_entries(content: &str) -> Vec<CrontabEntry> {
+    parse(content, true)
+}
+
+/// 导出 crontab 片段的方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum CrontabExportMode {
+    /// 与应用安装到 crontab 中的条目相同，由 runner 执行。
+    /// runner 依赖本机的应用数据库与安装路径，只能用于安装了本应用的这台机器
+    Wrapped,
+    /// 直接执行命令，不经过 runner，也不重定向输出，可以用于没有安装本应用的服务器
+    #[default]
+    Clean,
+}
+
+/// `MAILTO` 对之后的所有任务行生效，单独设置了 `MAILTO` 的任务块之后恢复全局的 `MAILTO`，
+/// 避免影响之后的任务以及用户在区段之后追加的配置。
+/// 没有全局 `MAILTO` 时 cron 的默认行为无法通过赋值恢复，改为把这些任务块排在最后
+fn join_blocks(mut blocks: Vec<(bool, String)>, global_mailto: Option<&str>) -> String {
+    let reset = global_mailto.map(|mailto| render_variable("MAILTO", mailto));
+    if reset.is_none() {
+        blocks.sort_by_key(|(has_mailto, _)| *has_mailto);
+    }
+    blocks
+        .into_iter()
+        .map(|(has_mailto, block)| match &reset {
+            Some(reset) if has_mailto => format!("{}\n{}", block, reset),
+            _ => block,
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 将任务导出为标准的 crontab 片段，只包含有执行计划的任务。
+/// 保留 `# JOB_ID:` 标记，导出的片段可以再次导入
+pub fn export_crontab(jobs: &[CronJob], mode: CrontabExportMode) -> Result<String, Error> {
+    let mut blocks = Vec::new();
+    let runner = match mode {
+        CrontabExportMode::Wrapped if jobs.iter().any(|job| job.is_scheduled()) => runner::install_runner()?,
+        _ => PathBuf::from(runner::RUNNER_BIN_NAME),
+    };
+
+    for job in jobs.iter().filter(|job| job.is_scheduled()) {
+        let block = match mode {
+            CrontabExportMode::Wrapped => render_job_block(job, &runner),
+            CrontabExportMode::Clean => {
+                // crontab 命令中的换行无法表示，`%` 会被 cron 当作换行，需要转义
+                if job.command.contains('\n') {
+                    return Err(Error::InvalidCommand(format!(
+                        "任务 {} 的命令包含多行，无法导出为 crontab",
+                        job.name.as_deref().unwrap_or(&job.id)
+                    )));
+                }
+                render_block(
+                    &job.id,
+                    job.name.as_deref(),
+                    job.mailto.as_deref(),
+                    &job.schedule,
+                    &job.command.trim().replace('%', "\\%"),
+                )
+            }
+        };
+        blocks.push((job.mailto.is_some(), block));
+    }
+
+    let mut content = join_blocks(blocks, None);
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+/// 读取当前用户的 crontab，没有 crontab 时返回空内容。
+/// 其他失败（权限不足、crontab 服务不可用等）返回错误，避免把读取失败当作空 crontab 覆盖用户配置
+pub fn read_crontab() -> Result<String, Error> {
+    let output = Command::new("crontab")
+        .arg("-l")
+        .output()
+        .map_err(|e| Error::CrontabError(e.to_string()))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("no crontab for") {
+            return Ok(String::new());
+        }
+        return Err(Error::CrontabError(format!("读取 crontab 失败: {}", stderr.trim())));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// crontab 内容的摘要，用于判断 crontab 是否在应用之外被修改
+pub fn content_hash(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.trim_end().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 写入当前用户的 crontab，内容通过标准输入传入，避免命令中的引号被 shell 解释
+pub fn write_crontab(content: &str) -> Result<(), Error> {
+    let mut child = Command::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::CrontabError(e.to_string()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(content.as_bytes())
+            .map_err(|e| Error::CrontabError(format!("写入 crontab 失败: {}", e)))?;
+    }
+
+    let status = child.wait()
+        .map_err(|e| Error::CrontabError(e.to_string()))?;
+    if !status.success() {
+        return Err(Error::CrontabError(format!("crontab 命令执行失败: {}", status)));
+    }
+
+    Ok(())
+}
+
+/// 单个任务在 crontab 中的配置块，由 `runner` 执行
+pub fn render_job_block(job: &CronJob, runner: &Path) -> String {
+    render_block(
+        &job.id,
+        job.name.as_deref(),
+        job.mailto.as_deref(),
+        &job.schedule,
+        &runner::crontab_command(job, runner),
+    )
+}
+
+/// 检查受管理区段的起止标记：有 `# BEGIN` 却没有对应的 `# END` 时无法确定区段范围，
+/// 继续处理会把区段之后的用户配置当作受管理内容删除
+fn check_section(content: &str) -> Result<(), Error> {
+    let mut in_section = false;
+    for line in content.lines() {
+        match line.trim() {
+            SECTION_BEGIN if in_section => {
+                return Err(Error::CrontabError(format!("crontab 中的 {} 重复出现，请手动修复后重试", SECTION_BEGIN)));
+            }
+            SECTION_BEGIN => in_section = true,
+            SECTION_END => in_section = false,
+            _ => {}
+        }
+    }
+    if in_section {
+        return Err(Error::CrontabError(format!("crontab 中缺少 {}，请手动修复后重试", SECTION_END)));
+    }
+    Ok(())
+}
+
+/// 移除受管理区段以及区段之外的旧格式任务块（`# JOB_ID:` 开头、空行结尾），保留用户自己的配置
+pub fn strip_managed(content: &str) -> Result<Vec<String>, Error> {
+    check_section(content)?;
+    let mut lines = Vec::new();
+    let mut in_section = false;
+    let mut in_block = false;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed == SECTION_BEGIN {
+            in_section = true;
+            continue;
+        }
+        if in_section {
+            if trimmed == SECTION_END {
+                in_section = false;
+            }
+            continue;
+        }
+
+        if line.starts_with("# JOB_ID:") {
+            in_block = true;
+            continue;
+        }
+        if in_block {
+            if trimmed.is_empty() {
+                in_block = false;
+            }
+            continue;
+        }
+
+        lines.push(line.to_string());
+    }
+
+    // 去掉末尾多余的空行，避免每次重写都累积空行
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    Ok(lines)
+}
+
+/// 根据当前 crontab 内容与数据库中的任务计算期望的 crontab：
+/// 保留用户配置，受管理区段只包含启用中且有执行计划的任务。
+/// `preserved` 中的任务保留 crontab 中原有的条目，不按数据库重新生成
+pub fn render(current: &str, jobs: &[CronJob], preserved: &HashSet<String>, runner: &Path) -> Result<String, Error> {
+    let mut lines = strip_managed(current)?;
+    let entries = parse_entries(current);
+    let preserved_entries = |job_id: &str| {
+        entries
+            .iter()
+            .filter(|entry| entry.job_id == job_id)
+            .map(|entry| (entry.mailto.is_some(), entry.render()))
+            .collect::<Vec<_>>()
+    };
+
+    let mut blocks = Vec::new();
+    for job in jobs {
+        if preserved.contains(&job.id) {
+            blocks.extend(preserved_entries(&job.id));
+        } else if job.is_active && job.is_scheduled() {
+            blocks.push((job.mailto.is_some(), render_job_block(job, runner)));
+        }
+    }
+
+    // 有启用了补执行策略的任务时，单独加入一行定时检查错过的执行
+    if jobs.iter().any(|job| job.is_active && job.catch_up && job.is_scheduled()) {
+        blocks.insert(0, (false, format!("{} {}", runner::CATCH_UP_SCHEDULE, runner::catch_up_command(runner))));
+    }
+
+    // 数据库中不存在的任务条目同样按原样保留
+    let job_ids: HashSet<&str> = jobs.iter().map(|job| job.id.as_str()).collect();
+    let mut unknown_ids: Vec<&str> = Vec::new();
+    for entry in &entries {
+        if preserved.contains(&entry.job_id)
+            && !job_ids.contains(entry.job_id.as_str())
+            && !unknown_ids.contains(&entry.job_id.as_str())
+        {
+            unknown_ids.push(&entry.job_id);
+        }
+    }
+    for job_id in unknown_ids {
+        blocks.extend(preserved_entries(job_id));
+    }
+
+    if !blocks.is_empty() {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(SECTION_BEGIN.to_string());
+        // 区段位于文件末尾，之前最后一次设置的 `MAILTO` 即全局值
+        let global_mailto = lines
+            .iter()
+            .rev()
+            .filter_map(|line| parse_variable(line.trim()))
+            .find(|(name, _)| name == "MAILTO")
+            .map(|(_, value)| value);
+        lines.push(join_blocks(blocks, global_mailto.as_deref()));
+        lines.push(SECTION_END.to_string());
+    }
+
+    let mut content = lines.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+/// 受管理区段之外的全局环境变量，按出现顺序排列
+pub fn parse_variables(content: &str) -> Result<Vec<CrontabVariable>, Error> {
+    Ok(strip_managed(content)?
+        .iter()
+        .filter_map(|line| parse_variable(line.trim()))
+        .map(|(name, value)| CrontabVariable { name, value })
+        .collect())
+}
+
+/// 检查变量名与值，值不能包含换行，同名变量只能出现一次
+pub fn validate_variables(variables: &[CrontabVariable]) -> Result<(), Error> {
+    let mut names = HashSet::new();
+    for variable in variables {
+        if !is_variable_name(&variable.name) {
+            return Err(Error::CrontabError(format!("无效的变量名: {}", variable.name)));
+        }
+        if variable.value.contains('\n') {
+            return Err(Error::CrontabError(format!("变量 {} 的值不能包含换行", variable.name)));
+        }
+        if !names.insert(variable.name.as_str()) {
+            return Err(Error::CrontabError(format!("变量 {} 重复", variable.name)));
+        }
+    }
+    Ok(())
+}
+
+/// 用 `variables` 替换受管理区段之外的全局环境变量：已有的变量原位更新，
+/// 不在列表中的变量删除，新增的变量写在文件开头。受管理区段与旧格式任务块保持不变
+pub fn set_variables(content: &str, variables: &[CrontabVariable]) -> Result<String, Error> {
+    validate_variables(variables)?;
+    check_section(content)?;
+
+    let mut lines = Vec::new();
+    let mut written = HashSet::new();
+    let mut in_section = false;
+    let mut in_block = false;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed == SECTION_BEGIN {
+            in_section = true;
+        }
+        if in_section {
+            if trimmed == SECTION_END {
+                in_section = false;
+            }
+            lines.push(line.to_string());
+            continue;
+        }
+
+        if line.starts_with("# JOB_ID:") {
+            in_block = true;
+        } else if trimmed.is_empty() {
+            in_block = false;
+        }
+
+        if !in_block {
+            if let Some((name, _)) = parse_variable(trimmed) {
+                if let Some(variable) = variables.iter().find(|variable| variable.name == name) {
+                    if written.insert(name) {
+                        lines.push(variable.render());
+                    }
+                }
+                continue;
+            }
+        }
+
+        lines.push(line.to_string());
+    }
+
+    let added: Vec<String> = variables
+        .iter()
+        .filter(|variable| !written.contains(&variable.name))
+        .map(CrontabVariable::render)
+        .collect();
+    lines.splice(0..0, added);
+
+    let mut content = lines.join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+/// 修改当前 crontab 的全局环境变量，内容有变化时写入。返回是否写入了新内容
+pub fn update_variables(variables: &[CrontabVariable]) -> Result<bool, Error> {
+    let current = read_crontab()?;
+    let desired = set_variables(&current, variables)?;
+    if desired.trim_end() == current.trim_end() {
+        return Ok(false);
+    }
+
+    write_crontab(&desired)?;
+    Ok(true)
+}
+
+/// 计算期望的 crontab，与当前内容不同时写入。返回是否写入了新内容。
+/// 与数据库不一致的条目保留原样，等待用户通过漂移报告处理；`overrides` 中的任务以数据库为准
+pub fn reconcile(jobs: &[CronJob], overrides: &[&str]) -> Result<bool, Error> {
+    let current = read_crontab()?;
+    let preserved: HashSet<String> = drift::detect_drift(&current, jobs)
+        .drifts
+        .into_iter()
+        .map(|drift| drift.job_id)
+        .filter(|job_id| !overrides.contains(&job_id.as_str()))
+        .collect();
+    // 需要写入 runner 条目时先确认 runner 可用，避免写入 cron 无法执行的命令
+    let needs_runner = jobs
+        .iter()
+        .any(|job| job.is_active && job.is_scheduled() && (job.catch_up || !preserved.contains(&job.id)));
+    let runner = if needs_runner {
+        runner::install_runner()?
+    } else {
+        PathBuf::from(runner::RUNNER_BIN_NAME)
+    };
+    let desired = render(&current, jobs, &preserved, &runner)?;
+
+    if desired.trim_end() == current.trim_end() {
+        return Ok(false);
+    }
+
+    if jobs.iter().any(|job| job.is_active) {
+        std::fs::create_dir_all(crate::log_dir())
+            .map_err(|e| Error::CrontabError(format!("创建输出目录失败: {}", e)))?;
+    }
+
+    write_crontab(&desired)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUNNER: &str = "/opt/crontab-ui/crontab-ui-runner";
+
+    fn job(id: &str, schedule: &str) -> CronJob {
+        CronJob {
+            id: id.to_string(),
+            name: Some(format!("任务 {}", id)),
+            schedule: schedule.to_string(),
+            command: "echo hi".to_string(),
+            is_active: true,
+            ..CronJob::default()
+        }
+    }
+
+    fn render_jobs(current: &str, jobs: &[CronJob]) -> Result<String, Error> {
+        render(current, jobs, &HashSet::new(), Path::new(RUNNER))
+    }
+
+    #[test]
+    fn parses_tagged_and_untagged_entries() {
+        let content = "\
+SHELL=/bin/bash
+# 用户自己的任务
+@daily /usr/bin/backup.sh
+
+# JOB_ID:job-1
+# NAME:清理
+MAILTO=ops@example.com
+*/5  * * * *   rm -rf /tmp/cache > '/home/u/.crontab/job-1.log' 2>&1
+";
+        let entries = parse_all_entries(content);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].job_id, "");
+        assert_eq!(entries[0].schedule, "@daily");
+        assert_eq!(entries[0].command_line, "/usr/bin/backup.sh");
+
+        assert_eq!(entries[1].job_id, "job-1");
+        assert_eq!(entries[1].name.as_deref(), Some("清理"));
+        assert_eq!(entries[1].mailto.as_deref(), Some("ops@example.com"));
+        assert_eq!(entries[1].schedule, "*/5 * * * *");
+        assert_eq!(entries[1].command().as_deref(), Some("rm -rf /tmp/cache"));
+
+        assert_eq!(parse_entries(content).len(), 1);
+    }
+
+    #[test]
+    fn rendered_jobs_parse_back_and_skip_the_catch_up_line() {
+        let mut first = job("job-1", "0 * * * *");
+        first.catch_up = true;
+        let content = render_jobs("", &[first.clone(), job("job-2", "")]).unwrap();
+
+        assert!(content.contains(runner::CATCH_UP_FLAG));
+        let entries = parse_all_entries(&content);
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].runs_job(&first));
+        assert_eq!(entries[0].command(), None);
+    }
+
+    #[test]
+    fn render_keeps_user_lines_and_replaces_the_section() {
+        let current = format!(
+            "MAILTO=me@example.com\n0 3 * * * /usr/bin/backup.sh\n\n{}\n# JOB_ID:old\n# NAME:\n* * * * * true\n{}\n",
+            SECTION_BEGIN, SECTION_END
+        );
+        let content = render_jobs(&current, &[job("job-1", "0 * * * *")]).unwrap();
+
+        assert!(content.starts_with("MAILTO=me@example.com\n0 3 * * * /usr/bin/backup.sh\n\n"));
+        assert!(!content.contains("JOB_ID:old"));
+        assert!(content.contains("# JOB_ID:job-1"));
+        assert!(content.trim_end().ends_with(SECTION_END));
+
+        // 没有需要写入的任务时整个区段被移除
+        let content = render_jobs(&content, &[]).unwrap();
+        assert_eq!(content, "MAILTO=me@example.com\n0 3 * * * /usr/bin/backup.sh\n");
+    }
+
+    #[test]
+    fn missing_section_end_is_an_error() {
+        let current = format!("{}\n# JOB_ID:job-1\n* * * * * true\n\n0 3 * * * /usr/bin/backup.sh\n", SECTION_BEGIN);
+        assert!(strip_managed(&current).is_err());
+        assert!(render_jobs(&current, &[]).is_err());
+        assert!(set_variables(&current, &[]).is_err());
+
+        let duplicated = format!("{}\n{}\n{}\n", SECTION_BEGIN, SECTION_BEGIN, SECTION_END);
+        assert!(strip_managed(&duplicated).is_err());
+    }
+
+    #[test]
+    fn set_variables_updates_in_place_and_leaves_jobs_alone() {
+        let current = format!(
+            "SHELL=/bin/sh\nPATH=/usr/bin\n0 3 * * * /usr/bin/backup.sh\n\n{}\n# JOB_ID:job-1\n# NAME:\nMAILTO=ops@example.com\n* * * * * true\n{}\n",
+            SECTION_BEGIN, SECTION_END
+        );
+        let variables = [
+            CrontabVariable { name: "PATH".to_string(), value: "/usr/local/bin:/usr/bin".to_string() },
+            CrontabVariable { name: "CRON_TZ".to_string(), value: "Asia/Shanghai".to_string() },
+        ];
+        let content = set_variables(&current, &variables).unwrap();
+
+        assert!(content.starts_with("CRON_TZ=Asia/Shanghai\nPATH=/usr/local/bin:/usr/bin\n0 3 * * *"));
+        assert!(!content.contains("SHELL="));
+        assert!(content.contains("MAILTO=ops@example.com"));
+        assert_eq!(parse_variables(&content).unwrap(), variables.iter().rev().cloned().collect::<Vec<_>>());
+
+        let invalid = [CrontabVariable { name: "1PATH".to_string(), value: String::new() }];
+        assert!(set_variables(&current, &invalid).is_err());
+    }
+
+    #[test]
+    fn per_job_mailto_is_reset_after_each_block() {
+        let mut mailed = job("job-1", "0 * * * *");
+        mailed.mailto = Some("ops@example.com".to_string());
+        let plain = job("job-2", "30 * * * *");
+        let jobs = [mailed.clone(), plain.clone()];
+
+        let content = render_jobs("MAILTO=me@example.com\n", &jobs).unwrap();
+        let section = &content[content.find(SECTION_BEGIN).unwrap()..];
+        let reset = section.find("MAILTO=me@example.com").expect("应恢复全局 MAILTO");
+        assert!(section.find("MAILTO=ops@example.com").unwrap() < reset);
+        assert!(reset < section.find("# JOB_ID:job-2").unwrap());
+
+        // 恢复的 MAILTO 不属于任何任务，解析与漂移检测结果不受影响
+        let entries = parse_entries(&content);
+        assert_eq!(entries[0].mailto.as_deref(), Some("ops@example.com"));
+        assert_eq!(entries[1].mailto, None);
+        assert!(drift::detect_drift(&content, &jobs).drifts.is_empty());
+
+        // 没有全局 MAILTO 时不写入恢复行，单独设置了 MAILTO 的任务排在最后
+        let exported = export_crontab(&jobs, CrontabExportMode::Clean).unwrap();
+        assert_eq!(exported.matches("MAILTO=").count(), 1);
+        assert!(exported.find("# JOB_ID:job-2").unwrap() < exported.find("MAILTO=ops@example.com").unwrap());
+        let entries = parse_entries(&exported);
+        assert_eq!(entries[0].mailto, None);
+        assert_eq!(entries[1].mailto.as_deref(), Some("ops@example.com"));
+
+        let content = render_jobs("", &jobs).unwrap();
+        assert_eq!(content.matches("MAILTO=").count(), 1);
+        assert!(drift::detect_drift(&content, &jobs).drifts.is_empty());
+    }
+}
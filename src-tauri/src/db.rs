@@ -1,11 +1,993 @@
-use rusqlite::{Connection, Result, params};
-use std::path::PathBuf;
-use std::sync::Mutex;
-use crate::{CronJob, JobHistory, JobStatus};
-use chrono::{DateTime, Utc};
+use rusqlite::types::{ToSql, Type};
+use rusqlite::{Connection, OpenFlags, Result, Row, TransactionBehavior, params};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use crate::capture::OutputStream;
+use crate::{CronJob, JobDependency, JobHistory, JobStatus, NotifyEvent, RunTrigger, SuccessRule, TriggerCondition, WebhookRule};
+use std::collections::HashMap;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
 
+/// 按顺序排列的数据库迁移，第 N 项把 `user_version` 从 N-1 升级到 N。
+/// 只能在末尾追加新迁移，已发布的迁移不可修改。
+const MIGRATIONS: &[&str] = &[
+    // v1: 初始表结构
+    "CREATE TABLE IF NOT EXISTS cron_jobs (
+        id TEXT PRIMARY KEY,
+        schedule TEXT NOT NULL,
+        command TEXT NOT NULL,
+        is_active INTEGER NOT NULL,
+        created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+
+    CREATE TABLE IF NOT EXISTS job_history (
+        id TEXT PRIMARY KEY,
+        job_id TEXT NOT NULL,
+        execution_time TEXT NOT NULL,
+        status TEXT NOT NULL,
+        output TEXT NOT NULL,
+        FOREIGN KEY(job_id) REFERENCES cron_jobs(id) ON DELETE CASCADE
+    );
+
+    CREATE INDEX IF NOT EXISTS idx_job_history_job_id ON job_history(job_id);
+    CREATE INDEX IF NOT EXISTS idx_job_history_execution_time ON job_history(execution_time);",
+    // v2: 任务名称
+    "ALTER TABLE cron_jobs ADD COLUMN name TEXT;",
+    // v3: 无法解码的执行记录隔离表
+    "CREATE TABLE job_history_quarantine (
+        id TEXT PRIMARY KEY,
+        job_id TEXT,
+        execution_time TEXT,
+        status TEXT,
+        output TEXT,
+        reason TEXT NOT NULL,
+        quarantined_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );",
+    // v4: 执行耗时与退出码
+    "ALTER TABLE job_history ADD COLUMN duration_ms INTEGER;
+    ALTER TABLE job_history ADD COLUMN exit_code INTEGER;
+    CREATE INDEX idx_job_history_status ON job_history(job_id, status);",
+    // v5: 错过执行后的补执行策略
+    "ALTER TABLE cron_jobs ADD COLUMN catch_up INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE cron_jobs ADD COLUMN max_lateness_minutes INTEGER;
+    ALTER TABLE job_history ADD COLUMN triggered_by TEXT NOT NULL DEFAULT 'Schedule';
+    CREATE TABLE catch_up_claims (
+        job_id TEXT NOT NULL,
+        scheduled_time TEXT NOT NULL,
+        claimed_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        PRIMARY KEY (job_id, scheduled_time),
+        FOREIGN KEY(job_id) REFERENCES cron_jobs(id) ON DELETE CASCADE
+    );",
+    // v6: 任务依赖关系，下游任务在上游任务执行完成后按条件触发
+    "CREATE TABLE job_dependencies (
+        downstream_id TEXT NOT NULL,
+        upstream_id TEXT NOT NULL,
+        condition TEXT NOT NULL,
+        PRIMARY KEY (downstream_id, upstream_id, condition),
+        FOREIGN KEY(downstream_id) REFERENCES cron_jobs(id) ON DELETE CASCADE,
+        FOREIGN KEY(upstream_id) REFERENCES cron_jobs(id) ON DELETE CASCADE
+    );
+    CREATE INDEX idx_job_dependencies_upstream ON job_dependencies(upstream_id, condition);",
+    // v7: 任务标签
+    "CREATE TABLE job_tags (
+        job_id TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (job_id, tag),
+        FOREIGN KEY(job_id) REFERENCES cron_jobs(id) ON DELETE CASCADE
+    );
+    CREATE INDEX idx_job_tags_tag ON job_tags(tag);",
+    // v8: 应用设置，值以 JSON 保存
+    "CREATE TABLE app_settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    // v9: 任务的 Webhook 通知规则
+    "CREATE TABLE job_webhooks (
+        job_id TEXT NOT NULL,
+        url TEXT NOT NULL,
+        event TEXT NOT NULL,
+        threshold INTEGER,
+        FOREIGN KEY(job_id) REFERENCES cron_jobs(id) ON DELETE CASCADE
+    );
+    CREATE INDEX idx_job_webhooks_job_id ON job_webhooks(job_id);",
+    // v10: 任务的邮件通知收件人
+    "CREATE TABLE job_email_recipients (
+        job_id TEXT NOT NULL,
+        address TEXT NOT NULL,
+        PRIMARY KEY (job_id, address),
+        FOREIGN KEY(job_id) REFERENCES cron_jobs(id) ON DELETE CASCADE
+    );",
+    // v11: 任务单独设置的 MAILTO
+    "ALTER TABLE cron_jobs ADD COLUMN mailto TEXT;",
+    // v12: 任务的成功规则，以及执行记录中决定结果的规则
+    "CREATE TABLE job_success_rules (
+        job_id TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        exit_code INTEGER,
+        pattern TEXT,
+        FOREIGN KEY(job_id) REFERENCES cron_jobs(id) ON DELETE CASCADE
+    );
+    CREATE INDEX idx_job_success_rules_job_id ON job_success_rules(job_id);
+    ALTER TABLE job_history ADD COLUMN matched_rule TEXT;",
+    // v13: 执行输出的全文索引。trigram 分词支持中文与任意子串，内容不重复存储，由触发器与 job_history 同步
+    "CREATE VIRTUAL TABLE job_history_fts USING fts5(
+        output,
+        content = 'job_history',
+        content_rowid = 'rowid',
+        tokenize = 'trigram'
+    );
+    CREATE TRIGGER job_history_fts_insert AFTER INSERT ON job_history BEGIN
+        INSERT INTO job_history_fts (rowid, output) VALUES (new.rowid, new.output);
+    END;
+    CREATE TRIGGER job_history_fts_delete AFTER DELETE ON job_history BEGIN
+        INSERT INTO job_history_fts (job_history_fts, rowid, output) VALUES ('delete', old.rowid, old.output);
+    END;
+    CREATE TRIGGER job_history_fts_update AFTER UPDATE OF output ON job_history BEGIN
+        INSERT INTO job_history_fts (job_history_fts, rowid, output) VALUES ('delete', old.rowid, old.output);
+        INSERT INTO job_history_fts (rowid, output) VALUES (new.rowid, new.output);
+    END;
+    INSERT INTO job_history_fts (job_history_fts) VALUES ('rebuild');",
+    // v14: 分开采集的 stdout 与 stderr，以及超出保存上限时省略的字节数
+    "ALTER TABLE job_history ADD COLUMN stdout TEXT;
+    ALTER TABLE job_history ADD COLUMN stderr TEXT;
+    ALTER TABLE job_history ADD COLUMN omitted_bytes INTEGER NOT NULL DEFAULT 0;",
+    // v15: 执行时间统一为 UTC、毫秒精度的 RFC3339（见 `time_key`），按字符串比较即可使用索引
+    "UPDATE job_history
+     SET execution_time = strftime('%Y-%m-%dT%H:%M:%fZ', execution_time)
+     WHERE strftime('%Y-%m-%dT%H:%M:%fZ', execution_time) IS NOT NULL;
+    UPDATE OR IGNORE catch_up_claims
+     SET scheduled_time = strftime('%Y-%m-%dT%H:%M:%fZ', scheduled_time)
+     WHERE strftime('%Y-%m-%dT%H:%M:%fZ', scheduled_time) IS NOT NULL;
+    CREATE INDEX idx_job_history_job_time ON job_history(job_id, execution_time);",
+    // v16: 任务最近一次由停用变为启用的时间，停用期间的计划执行不算错过
+    "ALTER TABLE cron_jobs ADD COLUMN enabled_at TEXT;",
+    // v17: 按任务静音系统通知，取代系统通知设置中的 `muted_job_ids`
+    "ALTER TABLE cron_jobs ADD COLUMN notifications_muted INTEGER NOT NULL DEFAULT 0;
+    UPDATE cron_jobs SET notifications_muted = 1
+     WHERE id IN (
+         SELECT muted.value
+         FROM app_settings, json_each(app_settings.value, '$.muted_job_ids') AS muted
+         WHERE app_settings.key = 'desktop_notifications' AND json_valid(app_settings.value)
+     );",
+    // v18: 执行记录改用显式的整数主键 `seq`。隐式 rowid 在 VACUUM 时可能被重新编号，
+    // 全文索引按 rowid 关联的记录会因此错位；`seq` 取原 rowid，保持索引与已记录的位置不变
+    "DROP TRIGGER job_history_fts_insert;
+    DROP TRIGGER job_history_fts_delete;
+    DROP TRIGGER job_history_fts_update;
+    DROP TABLE job_history_fts;
+    CREATE TABLE job_history_new (
+        seq INTEGER PRIMARY KEY,
+        id TEXT UNIQUE,
+        job_id TEXT NOT NULL,
+        execution_time TEXT NOT NULL,
+        status TEXT NOT NULL,
+        output TEXT NOT NULL,
+        duration_ms INTEGER,
+        exit_code INTEGER,
+        triggered_by TEXT NOT NULL DEFAULT 'Schedule',
+        matched_rule TEXT,
+        stdout TEXT,
+        stderr TEXT,
+        omitted_bytes INTEGER NOT NULL DEFAULT 0,
+        FOREIGN KEY(job_id) REFERENCES cron_jobs(id) ON DELETE CASCADE
+    );
+    INSERT INTO job_history_new (
+        seq, id, job_id, execution_time, status, output, duration_ms, exit_code,
+        triggered_by, matched_rule, stdout, stderr, omitted_bytes
+    )
+    SELECT rowid, id, job_id, execution_time, status, output, duration_ms, exit_code,
+        triggered_by, matched_rule, stdout, stderr, omitted_bytes
+    FROM job_history;
+    DROP TABLE job_history;
+    ALTER TABLE job_history_new RENAME TO job_history;
+    CREATE INDEX idx_job_history_job_id ON job_history(job_id);
+    CREATE INDEX idx_job_history_execution_time ON job_history(execution_time);
+    CREATE INDEX idx_job_history_status ON job_history(job_id, status);
+    CREATE INDEX idx_job_history_job_time ON job_history(job_id, execution_time);
+    CREATE VIRTUAL TABLE job_history_fts USING fts5(
+        output,
+        content = 'job_history',
+        content_rowid = 'seq',
+        tokenize = 'trigram'
+    );
+    CREATE TRIGGER job_history_fts_insert AFTER INSERT ON job_history BEGIN
+        INSERT INTO job_history_fts (rowid, output) VALUES (new.seq, new.output);
+    END;
+    CREATE TRIGGER job_history_fts_delete AFTER DELETE ON job_history BEGIN
+        INSERT INTO job_history_fts (job_history_fts, rowid, output) VALUES ('delete', old.seq, old.output);
+    END;
+    CREATE TRIGGER job_history_fts_update AFTER UPDATE OF output ON job_history BEGIN
+        INSERT INTO job_history_fts (job_history_fts, rowid, output) VALUES ('delete', old.seq, old.output);
+        INSERT INTO job_history_fts (rowid, output) VALUES (new.seq, new.output);
+    END;
+    INSERT INTO job_history_fts (job_history_fts) VALUES ('rebuild');",
+    // v19: 隔离表保留执行记录的全部列，修复时不丢失耗时、退出码与分开采集的输出
+    "ALTER TABLE job_history_quarantine ADD COLUMN duration_ms INTEGER;
+    ALTER TABLE job_history_quarantine ADD COLUMN exit_code INTEGER;
+    ALTER TABLE job_history_quarantine ADD COLUMN triggered_by TEXT;
+    ALTER TABLE job_history_quarantine ADD COLUMN matched_rule TEXT;
+    ALTER TABLE job_history_quarantine ADD COLUMN stdout TEXT;
+    ALTER TABLE job_history_quarantine ADD COLUMN stderr TEXT;
+    ALTER TABLE job_history_quarantine ADD COLUMN omitted_bytes INTEGER;",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("数据库版本 {found} 高于当前程序支持的版本 {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+
+    #[error("迁移到版本 {version} 失败: {source}")]
+    Failed {
+        version: u32,
+        #[source]
+        source: rusqlite::Error,
+    },
+
+    #[error("读取数据库版本失败: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+/// 等待其他连接（包括 runner 进程）释放锁的最长时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 连接池中最多保留的空闲只读连接数
+const MAX_IDLE_READERS: usize = 4;
+
+/// 数据库中无法解码的值
+#[derive(Debug, Serialize, Clone, PartialEq, thiserror::Error)]
+#[serde(tag = "kind", content = "detail")]
+pub enum DecodeError {
+    #[error("{column} 为空")]
+    Missing { column: String },
+
+    #[error("{column} 的类型不是文本")]
+    WrongType { column: String },
+
+    #[error("无效的执行时间 '{value}': {message}")]
+    InvalidExecutionTime { value: String, message: String },
+
+    #[error("未知的执行状态 '{0}'")]
+    UnknownStatus(String),
+
+    #[error("未知的触发方式 '{0}'")]
+    UnknownTrigger(String),
+
+    #[error("未知的依赖条件 '{0}'")]
+    UnknownCondition(String),
+
+    #[error("未知的通知时机 '{0}'")]
+    UnknownEvent(String),
+
+    #[error("未知的成功规则 '{0}'")]
+    UnknownSuccessRule(String),
+
+    #[error("无效的设置 {key}: {message}")]
+    InvalidSetting { key: String, message: String },
+
+    #[error("关联的任务 {0} 不存在")]
+    MissingJob(String),
+}
+
+/// 一条无法正常解码的执行记录。`id`、`job_id` 本身可能为空，修复时按 `seq` 定位
+#[derive(Debug, Serialize, Clone)]
+pub struct CorruptRow {
+    pub seq: i64,
+    pub id: Option<String>,
+    pub job_id: Option<String>,
+    pub problems: Vec<DecodeError>,
+    /// 所有问题的文字说明
+    pub reason: String,
+}
+
+/// 数据库检查结果
+#[derive(Debug, Serialize, Clone)]
+pub struct DbCheckReport {
+    /// `PRAGMA integrity_check` 报告的问题，数据库完好时为空
+    pub integrity_errors: Vec<String>,
+    pub corrupt_rows: Vec<CorruptRow>,
+    /// 全文索引中缺失或多余的执行记录数，不为 0 时修复会重建索引
+    pub fts_mismatches: usize,
+    /// 本次被移入隔离表的记录数
+    pub quarantined: usize,
+}
+
+fn conversion_error(column: usize, error: DecodeError) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(column, Type::Text, Box::new(error))
+}
+
+/// 错误是否由某条记录无法解码引起，而不是查询本身失败
+fn is_decode_error(error: &rusqlite::Error) -> bool {
+    matches!(
+        error,
+        rusqlite::Error::FromSqlConversionFailure(..) | rusqlite::Error::InvalidColumnType(..)
+    )
+}
+
+/// 收集列表查询的结果，跳过无法解码的记录，这些记录由 `check`/`repair` 报告与隔离
+fn collect_decodable<T>(rows: impl Iterator<Item = Result<T>>) -> Result<Vec<T>> {
+    let mut items = Vec::new();
+    for row in rows {
+        match row {
+            Ok(item) => items.push(item),
+            Err(e) if is_decode_error(&e) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(items)
+}
+
+/// 时间在数据库中的统一格式：UTC、毫秒精度、以 `Z` 结尾的 RFC3339，
+/// 例如 `2024-01-02T03:04:05.000Z`。格式定长，按字符串比较即按时间比较
+fn time_key(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// 只包含设置了的查询条件的 WHERE 子句与参数，参数按 `?` 出现的顺序排列。
+/// 相比 `?1 IS NULL OR ...` 的写法，SQLite 可以为实际的条件选用索引
+#[derive(Default)]
+struct Filter {
+    clauses: Vec<&'static str>,
+    params: Vec<Box<dyn ToSql>>,
+}
+
+impl Filter {
+    fn push(&mut self, clause: &'static str, param: impl ToSql + 'static) {
+        self.clauses.push(clause);
+        self.params.push(Box::new(param));
+    }
+
+    fn sql(&self) -> String {
+        if self.clauses.is_empty() {
+            "1".to_string()
+        } else {
+            self.clauses.join(" AND ")
+        }
+    }
+
+    /// 条件参数之后追加分页参数
+    fn params_with<'a>(&'a self, extra: &[&'a dyn ToSql]) -> Vec<&'a dyn ToSql> {
+        self.params.iter().map(|param| param.as_ref()).chain(extra.iter().copied()).collect()
+    }
+}
+
+/// 取出解码错误中的具体问题
+fn decode_problem(error: rusqlite::Error) -> Option<DecodeError> {
+    match error {
+        rusqlite::Error::FromSqlConversionFailure(_, _, source) => source.downcast::<DecodeError>().ok().map(|e| *e),
+        _ => None,
+    }
+}
+
+/// 检查时读取文本列，为空或类型不对时记录问题
+fn check_text(row: &Row, index: usize, column: &str, problems: &mut Vec<DecodeError>) -> Option<String> {
+    match row.get::<_, Option<String>>(index) {
+        Ok(Some(value)) => Some(value),
+        Ok(None) => {
+            problems.push(DecodeError::Missing { column: column.to_string() });
+            None
+        }
+        Err(_) => {
+            problems.push(DecodeError::WrongType { column: column.to_string() });
+            None
+        }
+    }
+}
+
+fn parse_execution_time(column: usize, value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| conversion_error(column, DecodeError::InvalidExecutionTime {
+            value: value.to_string(),
+            message: e.to_string(),
+        }))
+}
+
+fn parse_status(column: usize, value: &str) -> Result<JobStatus> {
+    match value {
+        "Success" => Ok(JobStatus::Success),
+        "Failed" => Ok(JobStatus::Failed),
+        _ => Err(conversion_error(column, DecodeError::UnknownStatus(value.to_string()))),
+    }
+}
+
+fn parse_trigger(column: usize, value: &str) -> Result<RunTrigger> {
+    match value {
+        "Schedule" => Ok(RunTrigger::Schedule),
+        "CatchUp" => Ok(RunTrigger::CatchUp),
+        "Manual" => Ok(RunTrigger::Manual),
+        "Dependency" => Ok(RunTrigger::Dependency),
+        _ => Err(conversion_error(column, DecodeError::UnknownTrigger(value.to_string()))),
+    }
+}
+
+fn trigger_str(trigger: &RunTrigger) -> &'static str {
+    match trigger {
+        RunTrigger::Schedule => "Schedule",
+        RunTrigger::CatchUp => "CatchUp",
+        RunTrigger::Manual => "Manual",
+        RunTrigger::Dependency => "Dependency",
+    }
+}
+
+fn parse_condition(column: usize, value: &str) -> Result<TriggerCondition> {
+    match value {
+        "OnSuccess" => Ok(TriggerCondition::OnSuccess),
+        "OnFailure" => Ok(TriggerCondition::OnFailure),
+        _ => Err(conversion_error(column, DecodeError::UnknownCondition(value.to_string()))),
+    }
+}
+
+fn condition_str(condition: &TriggerCondition) -> &'static str {
+    match condition {
+        TriggerCondition::OnSuccess => "OnSuccess",
+        TriggerCondition::OnFailure => "OnFailure",
+    }
+}
+
+/// 读取所有依赖关系，按下游任务分组
+fn load_dependencies(conn: &Connection) -> Result<HashMap<String, Vec<JobDependency>>> {
+    let mut stmt = conn.prepare(
+        "SELECT downstream_id, upstream_id, condition
+         FROM job_dependencies
+         ORDER BY rowid"
+    )?;
+
+    let mut dependencies: HashMap<String, Vec<JobDependency>> = HashMap::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        dependencies.entry(row.get(0)?).or_default().push(JobDependency {
+            upstream_id: row.get(1)?,
+            condition: parse_condition(2, &row.get::<_, String>(2)?)?,
+        });
+    }
+
+    Ok(dependencies)
+}
+
+/// 用任务当前的依赖列表替换数据库中的依赖关系
+fn save_dependencies(conn: &Connection, job: &CronJob) -> Result<()> {
+    conn.execute("DELETE FROM job_dependencies WHERE downstream_id = ?1", params![job.id])?;
+    for dependency in &job.depends_on {
+        conn.execute(
+            "INSERT OR IGNORE INTO job_dependencies (downstream_id, upstream_id, condition) VALUES (?1, ?2, ?3)",
+            params![job.id, dependency.upstream_id, condition_str(&dependency.condition)],
+        )?;
+    }
+    Ok(())
+}
+
+/// 通知时机在数据库中的表示，连续失败次数保存在 `threshold` 列
+fn event_columns(event: &NotifyEvent) -> (&'static str, Option<u32>) {
+    match event {
+        NotifyEvent::OnFailure => ("OnFailure", None),
+        NotifyEvent::OnSuccess => ("OnSuccess", None),
+        NotifyEvent::OnRecovery => ("OnRecovery", None),
+        NotifyEvent::OnConsecutiveFailures(count) => ("OnConsecutiveFailures", Some(*count)),
+    }
+}
+
+fn parse_event(column: usize, value: &str, threshold: Option<u32>) -> Result<NotifyEvent> {
+    match (value, threshold) {
+        ("OnFailure", _) => Ok(NotifyEvent::OnFailure),
+        ("OnSuccess", _) => Ok(NotifyEvent::OnSuccess),
+        ("OnRecovery", _) => Ok(NotifyEvent::OnRecovery),
+        ("OnConsecutiveFailures", Some(count)) => Ok(NotifyEvent::OnConsecutiveFailures(count)),
+        _ => Err(conversion_error(column, DecodeError::UnknownEvent(value.to_string()))),
+    }
+}
+
+/// 读取所有 Webhook 通知规则，按任务分组
+fn load_webhooks(conn: &Connection) -> Result<HashMap<String, Vec<WebhookRule>>> {
+    let mut stmt = conn.prepare("SELECT job_id, url, event, threshold FROM job_webhooks ORDER BY rowid")?;
+
+    let mut webhooks: HashMap<String, Vec<WebhookRule>> = HashMap::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        webhooks.entry(row.get(0)?).or_default().push(WebhookRule {
+            url: row.get(1)?,
+            event: parse_event(2, &row.get::<_, String>(2)?, row.get(3)?)?,
+        });
+    }
+
+    Ok(webhooks)
+}
+
+/// 用任务当前的通知规则替换数据库中的规则
+fn save_webhooks(conn: &Connection, job: &CronJob) -> Result<()> {
+    conn.execute("DELETE FROM job_webhooks WHERE job_id = ?1", params![job.id])?;
+    for rule in &job.webhooks {
+        let (event, threshold) = event_columns(&rule.event);
+        conn.execute(
+            "INSERT INTO job_webhooks (job_id, url, event, threshold) VALUES (?1, ?2, ?3, ?4)",
+            params![job.id, rule.url.trim(), event, threshold],
+        )?;
+    }
+    Ok(())
+}
+
+/// 成功规则在数据库中的表示，退出码与正则分别保存在 `exit_code`、`pattern` 列
+fn success_rule_columns(rule: &SuccessRule) -> (&'static str, Option<i32>, Option<&str>) {
+    match rule {
+        SuccessRule::ExitCode(code) => ("ExitCode", Some(*code), None),
+        SuccessRule::FailurePattern(pattern) => ("FailurePattern", None, Some(pattern)),
+        SuccessRule::RequiredPattern(pattern) => ("RequiredPattern", None, Some(pattern)),
+    }
+}
+
+fn parse_success_rule(column: usize, kind: &str, exit_code: Option<i32>, pattern: Option<String>) -> Result<SuccessRule> {
+    match (kind, exit_code, pattern) {
+        ("ExitCode", Some(code), _) => Ok(SuccessRule::ExitCode(code)),
+        ("FailurePattern", _, Some(pattern)) => Ok(SuccessRule::FailurePattern(pattern)),
+        ("RequiredPattern", _, Some(pattern)) => Ok(SuccessRule::RequiredPattern(pattern)),
+        _ => Err(conversion_error(column, DecodeError::UnknownSuccessRule(kind.to_string()))),
+    }
+}
+
+/// 读取所有成功规则，按任务分组
+fn load_success_rules(conn: &Connection) -> Result<HashMap<String, Vec<SuccessRule>>> {
+    let mut stmt = conn.prepare("SELECT job_id, kind, exit_code, pattern FROM job_success_rules ORDER BY rowid")?;
+
+    let mut rules: HashMap<String, Vec<SuccessRule>> = HashMap::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        rules.entry(row.get(0)?).or_default().push(
+            parse_success_rule(1, &row.get::<_, String>(1)?, row.get(2)?, row.get(3)?)?,
+        );
+    }
+
+    Ok(rules)
+}
+
+/// 用任务当前的成功规则替换数据库中的规则
+fn save_success_rules(conn: &Connection, job: &CronJob) -> Result<()> {
+    conn.execute("DELETE FROM job_success_rules WHERE job_id = ?1", params![job.id])?;
+    for rule in &job.success_rules {
+        let (kind, exit_code, pattern) = success_rule_columns(rule);
+        conn.execute(
+            "INSERT INTO job_success_rules (job_id, kind, exit_code, pattern) VALUES (?1, ?2, ?3, ?4)",
+            params![job.id, kind, exit_code, pattern],
+        )?;
+    }
+    Ok(())
+}
+
+/// 读取所有邮件收件人，按任务分组
+fn load_email_recipients(conn: &Connection) -> Result<HashMap<String, Vec<String>>> {
+    let mut stmt = conn.prepare("SELECT job_id, address FROM job_email_recipients ORDER BY rowid")?;
+
+    let mut recipients: HashMap<String, Vec<String>> = HashMap::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        recipients.entry(row.get(0)?).or_default().push(row.get(1)?);
+    }
+
+    Ok(recipients)
+}
+
+/// 用任务当前的收件人替换数据库中的收件人，地址去除首尾空白，空地址忽略
+fn save_email_recipients(conn: &Connection, job: &CronJob) -> Result<()> {
+    conn.execute("DELETE FROM job_email_recipients WHERE job_id = ?1", params![job.id])?;
+    for address in job.email_recipients.iter().map(|address| address.trim()).filter(|address| !address.is_empty()) {
+        conn.execute(
+            "INSERT OR IGNORE INTO job_email_recipients (job_id, address) VALUES (?1, ?2)",
+            params![job.id, address],
+        )?;
+    }
+    Ok(())
+}
+
+/// 读取所有标签，按任务分组
+fn load_tags(conn: &Connection) -> Result<HashMap<String, Vec<String>>> {
+    let mut stmt = conn.prepare("SELECT job_id, tag FROM job_tags ORDER BY tag")?;
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        tags.entry(row.get(0)?).or_default().push(row.get(1)?);
+    }
+
+    Ok(tags)
+}
+
+/// 用任务当前的标签替换数据库中的标签，标签去除首尾空白，空标签忽略
+fn save_tags(conn: &Connection, job: &CronJob) -> Result<()> {
+    conn.execute("DELETE FROM job_tags WHERE job_id = ?1", params![job.id])?;
+    for tag in job.tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+        conn.execute(
+            "INSERT OR IGNORE INTO job_tags (job_id, tag) VALUES (?1, ?2)",
+            params![job.id, tag],
+        )?;
+    }
+    Ok(())
+}
+
+/// 按条件读取任务，并附带依赖关系与标签
+fn load_jobs<P: rusqlite::Params>(conn: &Connection, filter: &str, params: P) -> Result<Vec<CronJob>> {
+    let mut stmt = conn.prepare(
+        &format!("SELECT {} FROM cron_jobs WHERE {}", JOB_COLUMNS, filter)
+    )?;
+
+    let mut jobs = stmt.query_map(params, job_from_row)?.collect::<Result<Vec<_>>>()?;
+
+    let mut dependencies = load_dependencies(conn)?;
+    let mut tags = load_tags(conn)?;
+    let mut webhooks = load_webhooks(conn)?;
+    let mut email_recipients = load_email_recipients(conn)?;
+    let mut success_rules = load_success_rules(conn)?;
+    for job in &mut jobs {
+        job.depends_on = dependencies.remove(&job.id).unwrap_or_default();
+        job.tags = tags.remove(&job.id).unwrap_or_default();
+        job.webhooks = webhooks.remove(&job.id).unwrap_or_default();
+        job.email_recipients = email_recipients.remove(&job.id).unwrap_or_default();
+        job.success_rules = success_rules.remove(&job.id).unwrap_or_default();
+    }
+
+    Ok(jobs)
+}
+
+/// `job_from_row` 对应的列
+const JOB_COLUMNS: &str = "id, name, schedule, command, is_active, catch_up, max_lateness_minutes, mailto, notifications_muted";
+
+fn job_from_row(row: &Row) -> Result<CronJob> {
+    Ok(CronJob {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        schedule: row.get(2)?,
+        command: row.get(3)?,
+        is_active: row.get::<_, i32>(4)? != 0,
+        catch_up: row.get::<_, i32>(5)? != 0,
+        max_lateness_minutes: row.get(6)?,
+        mailto: row.get(7)?,
+        notifications_muted: row.get::<_, i32>(8)? != 0,
+        ..CronJob::default()
+    })
+}
+
+/// 保存前去掉 `MAILTO` 首尾空白，空值视为未设置
+fn normalized_mailto(job: &CronJob) -> Option<&str> {
+    job.mailto.as_deref().map(str::trim).filter(|mailto| !mailto.is_empty())
+}
+
+fn status_str(status: &JobStatus) -> &'static str {
+    match status {
+        JobStatus::Success => "Success",
+        JobStatus::Failed => "Failed",
+    }
+}
+
+/// `history_from_row` 对应的列
+const HISTORY_COLUMNS: &str =
+    "id, job_id, execution_time, status, output, duration_ms, exit_code, triggered_by, matched_rule, stdout, stderr, omitted_bytes";
+
+/// 按 `HISTORY_COLUMNS` 的列顺序解码执行记录
+fn history_from_row(row: &Row) -> Result<JobHistory> {
+    Ok(JobHistory {
+        id: row.get(0)?,
+        job_id: row.get(1)?,
+        execution_time: parse_execution_time(2, &row.get::<_, String>(2)?)?,
+        status: parse_status(3, &row.get::<_, String>(3)?)?,
+        output: row.get(4)?,
+        duration_ms: row.get(5)?,
+        exit_code: row.get(6)?,
+        trigger: parse_trigger(7, &row.get::<_, String>(7)?)?,
+        matched_rule: row.get(8)?,
+        stdout: row.get(9)?,
+        stderr: row.get(10)?,
+        omitted_bytes: row.get(11)?,
+    })
+}
+
+/// 执行记录查询条件，所有条件均可选
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub job_id: Option<String>,
+    pub status: Option<JobStatus>,
+    /// 执行时间下限（含）
+    pub from: Option<DateTime<Utc>>,
+    /// 执行时间上限（不含）
+    pub to: Option<DateTime<Utc>>,
+    /// 在输出中搜索的文本，区分大小写
+    pub search: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    /// 是否返回完整输出，默认只返回摘要
+    pub include_output: bool,
+}
+
+/// 执行记录摘要，`output` 仅在查询要求时返回
+#[derive(Debug, Serialize, Clone)]
+pub struct HistorySummary {
+    pub id: String,
+    pub job_id: String,
+    pub execution_time: DateTime<Utc>,
+    pub status: JobStatus,
+    pub duration_ms: Option<i64>,
+    pub exit_code: Option<i32>,
+    pub trigger: RunTrigger,
+    pub matched_rule: Option<String>,
+    /// 是否分开采集了 stdout 与 stderr
+    pub separate_streams: bool,
+    pub omitted_bytes: u64,
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct HistoryPage {
+    /// 满足条件的记录总数（不受分页影响）
+    pub total: u64,
+    pub items: Vec<HistorySummary>,
+}
+
+/// 单个任务的执行汇总
+#[derive(Debug, Serialize, Clone)]
+pub struct JobAggregate {
+    pub job_id: String,
+    pub total_runs: u64,
+    pub success_count: u64,
+    pub failure_count: u64,
+    /// 成功率（0~1），没有执行记录时为空
+    pub success_rate: Option<f64>,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub avg_duration_ms: Option<f64>,
+    pub p95_duration_ms: Option<i64>,
+}
+
+/// 默认每页条数
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+impl HistoryQuery {
+    fn filter(&self) -> Filter {
+        let mut filter = Filter::default();
+        if let Some(job_id) = &self.job_id {
+            filter.push("job_id = ?", job_id.clone());
+        }
+        if let Some(status) = &self.status {
+            filter.push("status = ?", status_str(status));
+        }
+        if let Some(from) = &self.from {
+            filter.push("execution_time >= ?", time_key(from));
+        }
+        if let Some(to) = &self.to {
+            filter.push("execution_time < ?", time_key(to));
+        }
+        if let Some(search) = self.search.as_deref().filter(|search| !search.is_empty()) {
+            filter.push("instr(output, ?) > 0", search.to_string());
+        }
+        filter
+    }
+}
+
+/// 全文搜索的最少字符数，trigram 索引无法匹配更短的内容
+pub const MIN_SEARCH_CHARS: usize = 3;
+
+/// 搜索结果片段中标记匹配内容的前后缀
+pub const SNIPPET_MATCH_START: &str = "[[";
+pub const SNIPPET_MATCH_END: &str = "]]";
+
+/// 片段最多包含的 trigram 数，大致对应字符数
+const SNIPPET_TOKENS: u32 = 48;
+
+/// 日志全文搜索条件，`text` 按短语匹配，不区分大小写
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LogSearchQuery {
+    pub text: String,
+    pub job_id: Option<String>,
+    /// 执行时间下限（含）
+    pub from: Option<DateTime<Utc>>,
+    /// 执行时间上限（不含）
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// 一条匹配的执行记录，`snippet` 为匹配位置附近的输出
+#[derive(Debug, Serialize, Clone)]
+pub struct LogSearchHit {
+    pub history_id: String,
+    pub job_id: String,
+    pub job_name: Option<String>,
+    pub execution_time: DateTime<Utc>,
+    pub status: JobStatus,
+    pub snippet: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct LogSearchPage {
+    pub total: u64,
+    pub items: Vec<LogSearchHit>,
+}
+
+/// 把搜索内容转成 FTS5 短语，避免 `AND`、`*`、引号等被当作查询语法
+fn fts_phrase(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+impl LogSearchQuery {
+    fn filter(&self, phrase: String) -> Filter {
+        let mut filter = Filter::default();
+        filter.push("job_history_fts MATCH ?", phrase);
+        if let Some(job_id) = &self.job_id {
+            filter.push("h.job_id = ?", job_id.clone());
+        }
+        if let Some(from) = &self.from {
+            filter.push("h.execution_time >= ?", time_key(from));
+        }
+        if let Some(to) = &self.to {
+            filter.push("h.execution_time < ?", time_key(to));
+        }
+        filter
+    }
+}
+
+/// 单个任务在统计窗口内的指标
+#[derive(Debug, Serialize, Clone)]
+pub struct JobStats {
+    pub job_id: String,
+    pub name: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub runs: u64,
+    pub failures: u64,
+    /// 截至最近一次执行的连续失败次数
+    pub current_failure_streak: u64,
+    pub longest_failure_streak: u64,
+    pub mean_duration_ms: Option<f64>,
+    pub p95_duration_ms: Option<i64>,
+    /// 耗时随时间变化的线性回归斜率（毫秒/天），正数表示越来越慢
+    pub duration_trend_ms_per_day: Option<f64>,
+    /// 按计划触发的执行次数，不含手动执行、补执行与上游触发的执行
+    pub scheduled_runs: u64,
+    /// 按计划应执行的次数，无法根据表达式计算或次数过多时为空
+    pub expected_runs: Option<u64>,
+    pub missed_runs: Option<u64>,
+}
+
+/// 所有任务在统计窗口内的汇总指标
+#[derive(Debug, Serialize, Clone)]
+pub struct GlobalStats {
+    pub runs: u64,
+    pub failures: u64,
+    /// 最近一次执行失败的任务数
+    pub failing_jobs: u64,
+    pub mean_duration_ms: Option<f64>,
+    pub p95_duration_ms: Option<i64>,
+    pub expected_runs: u64,
+    pub missed_runs: u64,
+}
+
+/// 统计窗口内的执行记录，参数 ?1、?2 为窗口起止时间
+const WINDOW_CTE: &str = "windowed AS (
+        SELECT job_id, status, duration_ms, triggered_by,
+               julianday(execution_time) - julianday(?1) AS t
+        FROM job_history
+        WHERE execution_time >= ?1
+          AND execution_time < ?2
+    ),
+    ranked AS (
+        SELECT job_id, duration_ms,
+               ROW_NUMBER() OVER (PARTITION BY job_id ORDER BY duration_ms) AS job_rank,
+               COUNT(*) OVER (PARTITION BY job_id) AS job_cnt,
+               ROW_NUMBER() OVER (ORDER BY duration_ms) AS global_rank,
+               COUNT(*) OVER () AS global_cnt
+        FROM windowed
+        WHERE duration_ms IS NOT NULL
+    )";
+
+/// `created_at` 与 `enabled_at` 由 SQLite 的 `CURRENT_TIMESTAMP` 生成，格式为 UTC 的 `YYYY-MM-DD HH:MM:SS`
+fn parse_created_at(value: Option<String>) -> Option<DateTime<Utc>> {
+    value
+        .and_then(|value| NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S").ok())
+        .map(|time| DateTime::<Utc>::from_naive_utc_and_offset(time, Utc))
+}
+
+fn parse_optional_time(column: usize, value: Option<String>) -> Result<Option<DateTime<Utc>>> {
+    value.map(|value| parse_execution_time(column, &value)).transpose()
+}
+
+/// 最新的 schema 版本
+pub fn latest_version() -> u32 {
+    MIGRATIONS.len() as u32
+}
+
+fn user_version(conn: &Connection) -> Result<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// 引入迁移框架之前创建的数据库 `user_version` 为 0，需要根据现有表结构推断版本
+fn detect_legacy_version(conn: &Connection) -> Result<u32> {
+    let has_jobs_table = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'cron_jobs'")?
+        .exists([])?;
+    if !has_jobs_table {
+        return Ok(0);
+    }
+
+    let has_name_column = conn
+        .prepare("SELECT name FROM pragma_table_info('cron_jobs') WHERE name = 'name'")?
+        .exists([])?;
+
+    Ok(if has_name_column { 2 } else { 1 })
+}
+
+/// 将数据库升级到最新版本，每个迁移在独立事务中执行，失败时回滚并报告失败的版本
+pub fn migrate(conn: &mut Connection) -> std::result::Result<u32, MigrationError> {
+    let mut version = user_version(conn)?;
+    if version == 0 {
+        version = detect_legacy_version(conn)?;
+    }
+
+    let latest = latest_version();
+    if version > latest {
+        return Err(MigrationError::UnsupportedVersion { found: version, supported: latest });
+    }
+
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let target = index as u32 + 1;
+        let apply = |conn: &mut Connection| -> Result<()> {
+            // IMMEDIATE 事务保证 GUI 与 runner 同时启动时只有一方执行迁移
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            if user_version(&tx)? >= target {
+                return Ok(());
+            }
+            tx.execute_batch(sql)?;
+            tx.pragma_update(None, "user_version", target)?;
+            tx.commit()
+        };
+        apply(conn).map_err(|source| MigrationError::Failed { version: target, source })?;
+    }
+
+    // 推断出的旧版本也需要写回，避免下次重复推断
+    if user_version(conn)? < latest {
+        conn.pragma_update(None, "user_version", latest)?;
+    }
+
+    Ok(latest)
+}
+
+fn open_connection(path: &Path, flags: OpenFlags) -> Result<Connection> {
+    let conn = Connection::open_with_flags(path, flags)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    // 启用外键约束
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
+    Ok(conn)
+}
+
+/// 数据库句柄：写操作串行使用同一个写连接，读操作从只读连接池中取连接，
+/// 配合 WAL 模式，慢查询不会阻塞任务的启停，GUI 与 runner 进程也可以同时访问
 pub struct Database {
-    conn: Mutex<Connection>,
+    path: PathBuf,
+    writer: Mutex<Connection>,
+    readers: Mutex<Vec<Connection>>,
+}
+
+/// 从连接池借出的只读连接，释放时归还连接池
+pub struct PooledConnection<'a> {
+    db: &'a Database,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection already returned to pool")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            let mut readers = self.db.readers.lock().unwrap_or_else(|e| e.into_inner());
+            if readers.len() < MAX_IDLE_READERS {
+                readers.push(conn);
+            }
+        }
+    }
 }
 
 impl Database {
@@ -15,112 +997,246 @@ impl Database {
             std::fs::create_dir_all(&data_dir)?;
         }
 
-        let db_path = data_dir.join("crontab.db");
-        let is_new_db = !db_path.exists();
-        let conn = Connection::open(&db_path)?;
-
-        // 启用外键约束
-        conn.execute("PRAGMA foreign_keys = ON", [])?;
-
-        if is_new_db {
-            // 创建新数据库表
-            conn.execute_batch(
-                "BEGIN;
-                CREATE TABLE cron_jobs (
-                    id TEXT PRIMARY KEY,
-                    name TEXT,
-                    schedule TEXT NOT NULL,
-                    command TEXT NOT NULL,
-                    is_active INTEGER NOT NULL,
-                    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
-                );
-                
-                CREATE TABLE job_history (
-                    id TEXT PRIMARY KEY,
-                    job_id TEXT NOT NULL,
-                    execution_time TEXT NOT NULL,
-                    status TEXT NOT NULL,
-                    output TEXT NOT NULL,
-                    FOREIGN KEY(job_id) REFERENCES cron_jobs(id) ON DELETE CASCADE
-                );
-                
-                CREATE INDEX idx_job_history_job_id ON job_history(job_id);
-                CREATE INDEX idx_job_history_execution_time ON job_history(execution_time);
-                COMMIT;"
-            )?;
-        } else {
-            // 检查并更新现有表结构
-            let has_name_column = conn
-                .prepare("SELECT name FROM pragma_table_info('cron_jobs') WHERE name = 'name'")?
-                .exists([])?;
-
-            if !has_name_column {
-                conn.execute_batch(
-                    "BEGIN;
-                    ALTER TABLE cron_jobs ADD COLUMN name TEXT;
-                    COMMIT;"
-                )?;
-            }
-        }
+        Ok(Self::open(&data_dir.join("crontab.db"))?)
+    }
+
+    /// 打开指定路径的数据库文件并执行迁移
+    pub fn open(path: &Path) -> std::result::Result<Self, MigrationError> {
+        let mut writer = open_connection(path, OpenFlags::default())?;
+
+        // WAL 模式下读写互不阻塞，该设置持久化在数据库文件中
+        writer.query_row("PRAGMA journal_mode = WAL", [], |row| row.get::<_, String>(0))?;
+        writer.execute("PRAGMA synchronous = NORMAL", [])?;
 
-        Ok(Database { conn: Mutex::new(conn) })
+        migrate(&mut writer)?;
+
+        Ok(Database {
+            path: path.to_path_buf(),
+            writer: Mutex::new(writer),
+            readers: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// 获取写连接，所有写操作在进程内串行执行
+    fn writer(&self) -> MutexGuard<'_, Connection> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 从连接池借出一个只读连接，池为空时新建
+    fn reader(&self) -> Result<PooledConnection<'_>> {
+        let idle = self.readers.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => open_connection(
+                &self.path,
+                OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+            )?,
+        };
+        Ok(PooledConnection { db: self, conn: Some(conn) })
     }
 
     pub fn get_all_jobs(&self) -> Result<Vec<CronJob>> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.reader()?;
+        load_jobs(&conn, "1", [])
+    }
+
+    pub fn get_job(&self, id: &str) -> Result<Option<CronJob>> {
+        let conn = self.reader()?;
+        Ok(load_jobs(&conn, "id = ?1", [id])?.pop())
+    }
+
+    pub fn get_jobs_by_tag(&self, tag: &str) -> Result<Vec<CronJob>> {
+        let conn = self.reader()?;
+        load_jobs(&conn, "id IN (SELECT job_id FROM job_tags WHERE tag = ?1)", [tag])
+    }
+
+    /// 所有标签及其任务数
+    pub fn list_tags(&self) -> Result<Vec<(String, u64)>> {
+        let conn = self.reader()?;
         let mut stmt = conn.prepare(
-            "SELECT id, name, schedule, command, is_active FROM cron_jobs"
+            "SELECT tag, COUNT(*) FROM job_tags GROUP BY tag ORDER BY tag"
         )?;
-        
-        let jobs = stmt.query_map([], |row| {
-            Ok(CronJob {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                schedule: row.get(2)?,
-                command: row.get(3)?,
-                is_active: row.get::<_, i32>(4)? != 0,
-            })
-        })?;
 
-        jobs.collect()
+        let tags = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?;
+
+        tags.collect()
+    }
+
+    /// 依赖指定任务、且条件与其执行结果匹配的下游任务
+    pub fn get_downstream_jobs(&self, upstream_id: &str, condition: &TriggerCondition) -> Result<Vec<CronJob>> {
+        let conn = self.reader()?;
+        load_jobs(
+            &conn,
+            "id IN (SELECT downstream_id FROM job_dependencies WHERE upstream_id = ?1 AND condition = ?2)",
+            params![upstream_id, condition_str(condition)],
+        )
+    }
+
+    /// 任务开始按计划执行的时间：创建时间与最近一次启用时间中较晚的一个
+    pub fn get_job_active_since(&self, id: &str) -> Result<Option<DateTime<Utc>>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare("SELECT created_at, enabled_at FROM cron_jobs WHERE id = ?1")?;
+        let mut rows = stmt.query([id])?;
+        match rows.next()? {
+            Some(row) => Ok(parse_created_at(row.get(0)?).max(parse_created_at(row.get(1)?))),
+            None => Ok(None),
+        }
     }
 
     pub fn add_job(&self, job: &CronJob) -> Result<(), rusqlite::Error> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "INSERT INTO cron_jobs (id, name, schedule, command, is_active) VALUES (?, ?, ?, ?, ?)"
-        )?;
-        
-        stmt.execute(params![
-            job.id,
-            job.name,
-            job.schedule,
-            job.command,
-            job.is_active
-        ])?;
-        
-        Ok(())
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO cron_jobs (id, name, schedule, command, is_active, catch_up, max_lateness_minutes, mailto)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                job.id,
+                job.name,
+                job.schedule,
+                job.command,
+                job.is_active,
+                job.catch_up,
+                job.max_lateness_minutes,
+                normalized_mailto(job)
+            ],
+        )?;
+        save_dependencies(&tx, job)?;
+        save_tags(&tx, job)?;
+        save_webhooks(&tx, job)?;
+        save_email_recipients(&tx, job)?;
+        save_success_rules(&tx, job)?;
+        tx.commit()
     }
 
     pub fn update_job(&self, job: &CronJob) -> Result<(), rusqlite::Error> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "UPDATE cron_jobs SET name = ?, schedule = ?, command = ?, is_active = ? WHERE id = ?"
-        )?;
-        
-        stmt.execute(params![
-            job.name,
-            job.schedule,
-            job.command,
-            job.is_active,
-            job.id
-        ])?;
-        
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE cron_jobs
+             SET name = ?1, schedule = ?2, command = ?3, is_active = ?4, catch_up = ?5, max_lateness_minutes = ?6, mailto = ?7,
+                 enabled_at = CASE WHEN ?4 AND NOT is_active THEN CURRENT_TIMESTAMP ELSE enabled_at END
+             WHERE id = ?8",
+            params![
+                job.name,
+                job.schedule,
+                job.command,
+                job.is_active,
+                job.catch_up,
+                job.max_lateness_minutes,
+                normalized_mailto(job),
+                job.id
+            ],
+        )?;
+        save_dependencies(&tx, job)?;
+        save_tags(&tx, job)?;
+        save_webhooks(&tx, job)?;
+        save_email_recipients(&tx, job)?;
+        save_success_rules(&tx, job)?;
+        tx.commit()
+    }
+
+    /// 在同一事务中写入多个任务，已存在的任务按 ID 覆盖。
+    /// 先写入所有任务再写入依赖，这批任务之间可以互相依赖
+    pub fn save_jobs(&self, jobs: &[CronJob]) -> Result<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        for job in jobs {
+            tx.execute(
+                "INSERT INTO cron_jobs (id, name, schedule, command, is_active, catch_up, max_lateness_minutes, mailto)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                 ON CONFLICT(id) DO UPDATE SET
+                     name = excluded.name,
+                     schedule = excluded.schedule,
+                     command = excluded.command,
+                     is_active = excluded.is_active,
+                     enabled_at = CASE WHEN excluded.is_active AND NOT cron_jobs.is_active
+                                       THEN CURRENT_TIMESTAMP ELSE cron_jobs.enabled_at END,
+                     catch_up = excluded.catch_up,
+                     max_lateness_minutes = excluded.max_lateness_minutes,
+                     mailto = excluded.mailto",
+                params![
+                    job.id,
+                    job.name,
+                    job.schedule,
+                    job.command,
+                    job.is_active,
+                    job.catch_up,
+                    job.max_lateness_minutes,
+                    normalized_mailto(job)
+                ],
+            )?;
+        }
+        for job in jobs {
+            save_dependencies(&tx, job)?;
+            save_tags(&tx, job)?;
+            save_webhooks(&tx, job)?;
+            save_email_recipients(&tx, job)?;
+            save_success_rules(&tx, job)?;
+        }
+        tx.commit()
+    }
+
+    /// 在同一事务中批量启用或停用任务
+    pub fn set_jobs_active(&self, ids: &[String], is_active: bool) -> Result<()> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        for id in ids {
+            tx.execute(
+                "UPDATE cron_jobs
+                 SET is_active = ?1,
+                     enabled_at = CASE WHEN ?1 AND NOT is_active THEN CURRENT_TIMESTAMP ELSE enabled_at END
+                 WHERE id = ?2",
+                params![is_active, id],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// 读取一项应用设置，未设置时返回 `None`
+    pub fn get_setting<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare("SELECT value FROM app_settings WHERE key = ?1")?;
+        let mut rows = stmt.query([key])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+
+        let value: String = row.get(0)?;
+        serde_json::from_str(&value)
+            .map(Some)
+            .map_err(|e| conversion_error(0, DecodeError::InvalidSetting {
+                key: key.to_string(),
+                message: e.to_string(),
+            }))
+    }
+
+    /// 写入一项应用设置，已存在时覆盖
+    pub fn set_setting<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let value = serde_json::to_string(value)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        let conn = self.writer();
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
         Ok(())
     }
 
+    /// 在同一事务中批量删除任务，返回删除的任务数
+    pub fn delete_jobs(&self, ids: &[String]) -> Result<usize> {
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        let mut deleted = 0;
+        for id in ids {
+            deleted += tx.execute("DELETE FROM cron_jobs WHERE id = ?1", params![id])?;
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
     pub fn delete_job(&self, id: &str) -> Result<bool> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         let rows = conn.execute(
             "DELETE FROM cron_jobs WHERE id = ?1",
             params![id],
@@ -129,53 +1245,815 @@ impl Database {
     }
 
     pub fn get_job_history(&self, job_id: &str) -> Result<Vec<JobHistory>> {
-        let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, job_id, execution_time, status, output 
-             FROM job_history 
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM job_history
              WHERE job_id = ?1
-             ORDER BY execution_time DESC"
+             ORDER BY execution_time DESC",
+            HISTORY_COLUMNS
+        ))?;
+
+        let histories = stmt.query_map([job_id], history_from_row)?;
+
+        collect_decodable(histories)
+    }
+
+    /// 按 ID 读取一条执行记录
+    pub fn get_history(&self, id: &str) -> Result<Option<JobHistory>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM job_history WHERE id = ?1", HISTORY_COLUMNS))?;
+        let mut rows = stmt.query([id])?;
+        match rows.next()? {
+            Some(row) => Ok(Some(history_from_row(row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 任务截至 `until`（包含）最近 `limit` 次执行的状态，最近的在前。
+    /// 通知在执行结束后异步发送，期间可能已有新的执行记录，按本次执行时间截取
+    pub fn get_recent_statuses(&self, job_id: &str, until: &DateTime<Utc>, limit: u32) -> Result<Vec<JobStatus>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT status
+             FROM job_history
+             WHERE job_id = ?1 AND execution_time <= ?2
+             ORDER BY execution_time DESC
+             LIMIT ?3"
         )?;
-        
-        let histories = stmt.query_map([job_id], |row| {
-            Ok(JobHistory {
-                id: row.get(0)?,
-                job_id: row.get(1)?,
-                execution_time: DateTime::parse_from_rfc3339(&row.get::<_, String>(2)?)
-                    .unwrap()
-                    .with_timezone(&Utc),
-                status: if row.get::<_, String>(3)? == "Success" {
-                    JobStatus::Success
-                } else {
-                    JobStatus::Failed
-                },
-                output: row.get(4)?,
-            })
+
+        let statuses = stmt.query_map(params![job_id, time_key(until), limit], |row| {
+            parse_status(0, &row.get::<_, String>(0)?)
         })?;
 
-        histories.collect()
+        collect_decodable(statuses)
+    }
+
+    /// 一次执行保存的输出，`stream` 为空时返回合并的输出；没有分开采集时单独一路的输出为空
+    pub fn get_history_output(&self, history_id: &str, stream: Option<OutputStream>) -> Result<Option<String>> {
+        let column = match stream {
+            None => "output",
+            Some(OutputStream::Stdout) => "stdout",
+            Some(OutputStream::Stderr) => "stderr",
+        };
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM job_history WHERE id = ?1", column))?;
+        let mut rows = stmt.query([history_id])?;
+        match rows.next()? {
+            Some(row) => row.get(0),
+            None => Ok(None),
+        }
+    }
+
+    /// 最新一条执行记录的 `seq`，没有记录时为 0
+    pub fn latest_history_seq(&self) -> Result<i64> {
+        let conn = self.reader()?;
+        conn.query_row("SELECT COALESCE(MAX(seq), 0) FROM job_history", [], |row| row.get(0))
+    }
+
+    /// `seq` 大于 `after` 的失败记录及其 `seq`，按写入顺序排列，最多 `limit` 条。
+    /// 无法解码的记录返回 `None`，调用方可以跳过它继续处理之后的记录
+    pub fn get_failures_after(&self, after: i64, limit: u32) -> Result<Vec<(i64, Option<JobHistory>)>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, seq
+             FROM job_history
+             WHERE seq > ?1 AND status = 'Failed'
+             ORDER BY seq
+             LIMIT ?2",
+            HISTORY_COLUMNS
+        ))?;
+
+        let mut rows = stmt.query(params![after, limit])?;
+        let mut failures = Vec::new();
+        while let Some(row) = rows.next()? {
+            let seq: i64 = row.get(12)?;
+            match history_from_row(row) {
+                Ok(history) => failures.push((seq, Some(history))),
+                Err(e) if is_decode_error(&e) => failures.push((seq, None)),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(failures)
+    }
+
+    /// 单独设置任务是否发送系统通知
+    pub fn set_job_notifications_muted(&self, id: &str, muted: bool) -> Result<bool> {
+        let updated = self.writer().execute(
+            "UPDATE cron_jobs SET notifications_muted = ?1 WHERE id = ?2",
+            params![muted, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// 任务在 `[from, to)` 区间内按计划触发的执行时间，按时间升序
+    pub fn get_scheduled_run_times(
+        &self,
+        job_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<DateTime<Utc>>> {
+        let conn = self.reader()?;
+        let mut stmt = conn.prepare(
+            "SELECT execution_time
+             FROM job_history
+             WHERE job_id = ?1
+               AND triggered_by = 'Schedule'
+               AND execution_time >= ?2
+               AND execution_time < ?3
+             ORDER BY execution_time"
+        )?;
+
+        let times = stmt.query_map(
+            params![job_id, time_key(&from), time_key(&to)],
+            |row| parse_execution_time(0, &row.get::<_, String>(0)?),
+        )?;
+
+        collect_decodable(times)
+    }
+
+    /// 登记某次计划执行的补执行，只有首次登记成功的调用方（GUI 或某个 runner 进程）返回 true
+    pub fn claim_catch_up(&self, job_id: &str, scheduled_time: DateTime<Utc>) -> Result<bool> {
+        let conn = self.writer();
+        let rows = conn.execute(
+            "INSERT OR IGNORE INTO catch_up_claims (job_id, scheduled_time) VALUES (?1, ?2)",
+            params![job_id, time_key(&scheduled_time)],
+        )?;
+        Ok(rows > 0)
     }
 
     pub fn add_history(&self, history: &JobHistory) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+        let conn = self.writer();
         conn.execute(
-            "INSERT INTO job_history (id, job_id, execution_time, status, output)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
+            &format!("INSERT INTO job_history ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)", HISTORY_COLUMNS),
             params![
                 history.id,
                 history.job_id,
-                history.execution_time.to_rfc3339(),
-                match history.status {
-                    JobStatus::Success => "Success",
-                    JobStatus::Failed => "Failed",
-                },
-                history.output
+                time_key(&history.execution_time),
+                status_str(&history.status),
+                history.output,
+                history.duration_ms,
+                history.exit_code,
+                trigger_str(&history.trigger),
+                history.matched_rule,
+                history.stdout,
+                history.stderr,
+                history.omitted_bytes
             ],
         )?;
         Ok(())
     }
+
+    /// 按条件分页查询执行记录，按执行时间倒序
+    pub fn query_history(&self, query: &HistoryQuery) -> Result<HistoryPage> {
+        let conn = self.reader()?;
+        let filter = query.filter();
+
+        let total: i64 = conn.query_row(
+            &format!("SELECT COUNT(*) FROM job_history WHERE {}", filter.sql()),
+            filter.params_with(&[]).as_slice(),
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT id, job_id, execution_time, status, duration_ms, exit_code, triggered_by,
+                    {}, matched_rule, stdout IS NOT NULL, omitted_bytes
+             FROM job_history
+             WHERE {}
+             ORDER BY execution_time DESC
+             LIMIT ? OFFSET ?",
+            if query.include_output { "output" } else { "NULL" },
+            filter.sql()
+        ))?;
+
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let offset = query.offset.unwrap_or(0);
+        let items = stmt.query_map(filter.params_with(&[&limit, &offset]).as_slice(), |row| {
+            Ok(HistorySummary {
+                id: row.get(0)?,
+                job_id: row.get(1)?,
+                execution_time: parse_execution_time(2, &row.get::<_, String>(2)?)?,
+                status: parse_status(3, &row.get::<_, String>(3)?)?,
+                duration_ms: row.get(4)?,
+                exit_code: row.get(5)?,
+                trigger: parse_trigger(6, &row.get::<_, String>(6)?)?,
+                output: row.get(7)?,
+                matched_rule: row.get(8)?,
+                separate_streams: row.get(9)?,
+                omitted_bytes: row.get(10)?,
+            })
+        })?;
+
+        Ok(HistoryPage {
+            total: total as u64,
+            items: collect_decodable(items)?,
+        })
+    }
+
+    /// 在所有执行输出中全文搜索，按执行时间倒序。搜索内容少于 `MIN_SEARCH_CHARS` 个字符时没有结果
+    pub fn search_logs(&self, query: &LogSearchQuery) -> Result<LogSearchPage> {
+        let text = query.text.trim();
+        if text.chars().count() < MIN_SEARCH_CHARS {
+            return Ok(LogSearchPage { total: 0, items: Vec::new() });
+        }
+
+        let conn = self.reader()?;
+        let filter = query.filter(fts_phrase(text));
+
+        let total: i64 = conn.query_row(
+            &format!(
+                "SELECT COUNT(*)
+                 FROM job_history_fts JOIN job_history h ON h.seq = job_history_fts.rowid
+                 WHERE {}",
+                filter.sql()
+            ),
+            filter.params_with(&[]).as_slice(),
+            |row| row.get(0),
+        )?;
+
+        // 片段参数出现在 WHERE 之前，直接写入常量，`?` 只用于条件与分页
+        let mut stmt = conn.prepare(&format!(
+            "SELECT h.id, h.job_id, j.name, h.execution_time, h.status,
+                    snippet(job_history_fts, 0, '{}', '{}', '…', {})
+             FROM job_history_fts
+             JOIN job_history h ON h.seq = job_history_fts.rowid
+             LEFT JOIN cron_jobs j ON j.id = h.job_id
+             WHERE {}
+             ORDER BY h.execution_time DESC
+             LIMIT ? OFFSET ?",
+            SNIPPET_MATCH_START,
+            SNIPPET_MATCH_END,
+            SNIPPET_TOKENS,
+            filter.sql()
+        ))?;
+
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let offset = query.offset.unwrap_or(0);
+        let items = stmt.query_map(filter.params_with(&[&limit, &offset]).as_slice(), |row| {
+            Ok(LogSearchHit {
+                history_id: row.get(0)?,
+                job_id: row.get(1)?,
+                job_name: row.get(2)?,
+                execution_time: parse_execution_time(3, &row.get::<_, String>(3)?)?,
+                status: parse_status(4, &row.get::<_, String>(4)?)?,
+                snippet: row.get(5)?,
+            })
+        })?;
+
+        Ok(LogSearchPage {
+            total: total as u64,
+            items: collect_decodable(items)?,
+        })
+    }
+
+    /// 统计每个任务的执行情况，`job_id` 为空时返回所有任务
+    pub fn get_job_aggregates(&self, job_id: Option<&str>) -> Result<Vec<JobAggregate>> {
+        let conn = self.reader()?;
+        // p95 使用 nearest-rank 算法：排序后第 ceil(0.95 * n) 个值。
+        // 指定任务时在排序前过滤，避免为单个任务对整张执行记录表排序
+        let mut stmt = conn.prepare(
+            "WITH ranked AS (
+                SELECT job_id, duration_ms,
+                       ROW_NUMBER() OVER (PARTITION BY job_id ORDER BY duration_ms) AS rank,
+                       COUNT(*) OVER (PARTITION BY job_id) AS cnt
+                FROM job_history
+                WHERE duration_ms IS NOT NULL AND (?1 IS NULL OR job_id = ?1)
+            ),
+            p95 AS (
+                SELECT job_id, MIN(duration_ms) AS p95_duration_ms
+                FROM ranked
+                WHERE rank >= 0.95 * cnt
+                GROUP BY job_id
+            )
+            SELECT j.id,
+                   COUNT(h.id),
+                   COALESCE(SUM(h.status = 'Success'), 0),
+                   COALESCE(SUM(h.status = 'Failed'), 0),
+                   MAX(h.execution_time),
+                   MAX(CASE WHEN h.status = 'Success' THEN h.execution_time END),
+                   AVG(h.duration_ms),
+                   p95.p95_duration_ms
+            FROM cron_jobs j
+            LEFT JOIN job_history h ON h.job_id = j.id
+            LEFT JOIN p95 ON p95.job_id = j.id
+            WHERE ?1 IS NULL OR j.id = ?1
+            GROUP BY j.id
+            ORDER BY j.created_at"
+        )?;
+
+        let aggregates = stmt.query_map(params![job_id], |row| {
+            let total_runs: i64 = row.get(1)?;
+            let success_count: i64 = row.get(2)?;
+            Ok(JobAggregate {
+                job_id: row.get(0)?,
+                total_runs: total_runs as u64,
+                success_count: success_count as u64,
+                failure_count: row.get::<_, i64>(3)? as u64,
+                success_rate: (total_runs > 0).then(|| success_count as f64 / total_runs as f64),
+                last_run: parse_optional_time(4, row.get(4)?)?,
+                last_success: parse_optional_time(5, row.get(5)?)?,
+                avg_duration_ms: row.get(6)?,
+                p95_duration_ms: row.get(7)?,
+            })
+        })?;
+
+        aggregates.collect()
+    }
+
+    /// 统计 `[from, to)` 窗口内每个任务的执行指标，`expected_runs` 与 `missed_runs` 由调用方根据计划补充
+    pub fn get_job_stats(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<JobStats>> {
+        let conn = self.reader()?;
+        // 连续失败使用 gaps-and-islands：同一状态的连续记录两个行号之差相同
+        let mut stmt = conn.prepare(&format!(
+            "WITH {},
+            islands AS (
+                SELECT job_id, status, t,
+                       ROW_NUMBER() OVER (PARTITION BY job_id ORDER BY t)
+                       - ROW_NUMBER() OVER (PARTITION BY job_id, status ORDER BY t) AS grp
+                FROM windowed
+            ),
+            streaks AS (
+                SELECT job_id, COUNT(*) AS len, MAX(t) AS last_t
+                FROM islands
+                WHERE status = 'Failed'
+                GROUP BY job_id, grp
+            ),
+            totals AS (
+                SELECT job_id,
+                       COUNT(*) AS runs,
+                       SUM(status = 'Failed') AS failures,
+                       SUM(triggered_by = 'Schedule') AS scheduled_runs,
+                       MAX(t) AS last_t,
+                       AVG(duration_ms) AS mean_duration,
+                       COUNT(duration_ms) AS n,
+                       SUM(CASE WHEN duration_ms IS NOT NULL THEN t END) AS sx,
+                       SUM(duration_ms) AS sy,
+                       SUM(t * duration_ms) AS sxy,
+                       SUM(CASE WHEN duration_ms IS NOT NULL THEN t * t END) AS sxx
+                FROM windowed
+                GROUP BY job_id
+            ),
+            p95 AS (
+                SELECT job_id, MIN(duration_ms) AS value
+                FROM ranked
+                WHERE job_rank >= 0.95 * job_cnt
+                GROUP BY job_id
+            )
+            SELECT j.id, j.name, j.created_at,
+                   COALESCE(totals.runs, 0),
+                   COALESCE(totals.failures, 0),
+                   COALESCE((SELECT s.len FROM streaks s WHERE s.job_id = j.id AND s.last_t = totals.last_t), 0),
+                   COALESCE((SELECT MAX(s.len) FROM streaks s WHERE s.job_id = j.id), 0),
+                   totals.mean_duration,
+                   p95.value,
+                   CASE WHEN totals.n > 1 AND totals.n * totals.sxx - totals.sx * totals.sx > 0
+                        THEN (totals.n * totals.sxy - totals.sx * totals.sy)
+                             / (totals.n * totals.sxx - totals.sx * totals.sx)
+                   END,
+                   COALESCE(totals.scheduled_runs, 0)
+            FROM cron_jobs j
+            LEFT JOIN totals ON totals.job_id = j.id
+            LEFT JOIN p95 ON p95.job_id = j.id
+            ORDER BY j.created_at",
+            WINDOW_CTE
+        ))?;
+
+        let stats = stmt.query_map(params![time_key(&from), time_key(&to)], |row| {
+            Ok(JobStats {
+                job_id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: parse_created_at(row.get(2)?),
+                runs: row.get::<_, i64>(3)? as u64,
+                failures: row.get::<_, i64>(4)? as u64,
+                current_failure_streak: row.get::<_, i64>(5)? as u64,
+                longest_failure_streak: row.get::<_, i64>(6)? as u64,
+                mean_duration_ms: row.get(7)?,
+                p95_duration_ms: row.get(8)?,
+                duration_trend_ms_per_day: row.get(9)?,
+                scheduled_runs: row.get::<_, i64>(10)? as u64,
+                expected_runs: None,
+                missed_runs: None,
+            })
+        })?;
+
+        stats.collect()
+    }
+
+    /// 统计 `[from, to)` 窗口内所有任务的汇总指标，期望执行次数由调用方补充
+    pub fn get_global_stats(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<GlobalStats> {
+        let conn = self.reader()?;
+        conn.query_row(
+            &format!(
+                "WITH {},
+                latest AS (
+                    SELECT job_id, status,
+                           ROW_NUMBER() OVER (PARTITION BY job_id ORDER BY t DESC) AS rn
+                    FROM windowed
+                )
+                SELECT (SELECT COUNT(*) FROM windowed),
+                       (SELECT COUNT(*) FROM windowed WHERE status = 'Failed'),
+                       (SELECT COUNT(*) FROM latest WHERE rn = 1 AND status = 'Failed'),
+                       (SELECT AVG(duration_ms) FROM windowed),
+                       (SELECT MIN(duration_ms) FROM ranked WHERE global_rank >= 0.95 * global_cnt)",
+                WINDOW_CTE
+            ),
+            params![time_key(&from), time_key(&to)],
+            |row| {
+                Ok(GlobalStats {
+                    runs: row.get::<_, i64>(0)? as u64,
+                    failures: row.get::<_, i64>(1)? as u64,
+                    failing_jobs: row.get::<_, i64>(2)? as u64,
+                    mean_duration_ms: row.get(3)?,
+                    p95_duration_ms: row.get(4)?,
+                    expected_runs: 0,
+                    missed_runs: 0,
+                })
+            },
+        )
+    }
+
+    /// 检查数据库完整性并找出无法解码的执行记录
+    pub fn check(&self) -> Result<DbCheckReport> {
+        let conn = self.reader()?;
+
+        let integrity_errors = conn
+            .prepare("PRAGMA integrity_check")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|message| message != "ok")
+            .collect();
+
+        let mut stmt = conn.prepare(
+            "SELECT h.seq, h.id, h.job_id, h.execution_time, h.status, h.triggered_by, j.id IS NOT NULL
+             FROM job_history h
+             LEFT JOIN cron_jobs j ON j.id = h.job_id"
+        )?;
+
+        let mut corrupt_rows = Vec::new();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let mut problems = Vec::new();
+            let id = check_text(row, 1, "id", &mut problems);
+            let job_id = check_text(row, 2, "job_id", &mut problems);
+            if let Some(value) = check_text(row, 3, "execution_time", &mut problems) {
+                problems.extend(parse_execution_time(3, &value).err().and_then(decode_problem));
+            }
+            if let Some(value) = check_text(row, 4, "status", &mut problems) {
+                problems.extend(parse_status(4, &value).err().and_then(decode_problem));
+            }
+            if let Some(value) = check_text(row, 5, "triggered_by", &mut problems) {
+                problems.extend(parse_trigger(5, &value).err().and_then(decode_problem));
+            }
+            if let Some(job_id) = &job_id {
+                if !row.get::<_, bool>(6)? {
+                    problems.push(DecodeError::MissingJob(job_id.clone()));
+                }
+            }
+
+            if !problems.is_empty() {
+                let reason = problems.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ");
+                corrupt_rows.push(CorruptRow { seq: row.get(0)?, id, job_id, problems, reason });
+            }
+        }
+
+        // 外部内容表的索引为每条已索引的记录在 `_docsize` 中保留一行，与执行记录逐一对照即可
+        // 发现缺失或多余的索引项，不需要写事务
+        let fts_mismatches: i64 = conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM job_history h
+                     WHERE NOT EXISTS (SELECT 1 FROM job_history_fts_docsize d WHERE d.id = h.seq))
+                  + (SELECT COUNT(*) FROM job_history_fts_docsize d
+                     WHERE NOT EXISTS (SELECT 1 FROM job_history h WHERE h.seq = d.id))",
+            [],
+            |row| row.get(0),
+        )?;
+
+        Ok(DbCheckReport {
+            integrity_errors,
+            corrupt_rows,
+            fts_mismatches: fts_mismatches as usize,
+            quarantined: 0,
+        })
+    }
+
+    /// 将无法解码的执行记录移入 `job_history_quarantine`，原始数据保留以便人工恢复；
+    /// 日志全文索引与执行记录不一致时重建索引。没有需要修复的内容时不写数据库
+    pub fn repair(&self) -> Result<DbCheckReport> {
+        let mut report = self.check()?;
+        if report.corrupt_rows.is_empty() && report.fts_mismatches == 0 {
+            return Ok(report);
+        }
+
+        let mut conn = self.writer();
+        let tx = conn.transaction()?;
+        for row in &report.corrupt_rows {
+            tx.execute(
+                "INSERT OR REPLACE INTO job_history_quarantine (
+                     id, job_id, execution_time, status, output, duration_ms, exit_code,
+                     triggered_by, matched_rule, stdout, stderr, omitted_bytes, reason
+                 )
+                 SELECT id, job_id, execution_time, status, output, duration_ms, exit_code,
+                        triggered_by, matched_rule, stdout, stderr, omitted_bytes, ?2
+                 FROM job_history WHERE seq = ?1",
+                params![row.seq, row.reason],
+            )?;
+            report.quarantined += tx.execute("DELETE FROM job_history WHERE seq = ?1", params![row.seq])?;
+        }
+        // 删除记录时触发器会同步索引；索引本身已不一致时，按删除后的执行记录整体重建
+        if report.fts_mismatches > 0 {
+            tx.execute("INSERT INTO job_history_fts (job_history_fts) VALUES ('rebuild')", [])?;
+        }
+        tx.commit()?;
+
+        Ok(report)
+    }
 }
 
-// 实现 Send 和 Sync
-unsafe impl Send for Database {}
-unsafe impl Sync for Database {} 
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempDir;
+
+    /// 按前 `version` 个迁移建出旧版本的数据库，并写入一个任务与一条执行记录。
+    /// 只写入 v1 就有的列，各版本都能插入
+    fn create_fixture(version: usize, user_version: u32) -> TempDir {
+        let dir = TempDir::new();
+        let conn = Connection::open(dir.path().join("crontab.db")).unwrap();
+        for sql in &MIGRATIONS[..version] {
+            conn.execute_batch(sql).unwrap();
+        }
+        conn.pragma_update(None, "user_version", user_version).unwrap();
+
+        if version > 0 {
+            conn.execute(
+                "INSERT INTO cron_jobs (id, schedule, command, is_active) VALUES ('job-1', '*/5 * * * *', 'echo hi', 1)",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO job_history (id, job_id, execution_time, status, output)
+                 VALUES ('run-1', 'job-1', '2024-01-02T03:04:05+00:00', 'Success', 'hi')",
+                [],
+            )
+            .unwrap();
+        }
+        dir
+    }
+
+    fn assert_fixture_readable(db: &Database) {
+        let jobs = db.get_all_jobs().unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, "job-1");
+        assert_eq!(jobs[0].command, "echo hi");
+        assert!(jobs[0].is_active);
+
+        let history = db.get_job_history("job-1").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].output, "hi");
+        assert!(matches!(history[0].status, JobStatus::Success));
+    }
+
+    fn stored_version(dir: &Path) -> u32 {
+        let conn = Connection::open(dir.join("crontab.db")).unwrap();
+        user_version(&conn).unwrap()
+    }
+
+    #[test]
+    fn migrates_every_version_to_latest() {
+        for version in 0..=MIGRATIONS.len() {
+            let dir = create_fixture(version, version as u32);
+            let db = Database::new(dir.path().to_path_buf())
+                .unwrap_or_else(|e| panic!("打开 v{} 数据库失败: {}", version, e));
+            if version > 0 {
+                assert_fixture_readable(&db);
+            } else {
+                assert!(db.get_all_jobs().unwrap().is_empty());
+            }
+            drop(db);
+            assert_eq!(stored_version(dir.path()), latest_version(), "v{}", version);
+        }
+    }
+
+    #[test]
+    fn detects_legacy_databases_without_user_version() {
+        // 引入迁移框架之前的数据库：v1 没有 name 列，v2 有
+        for version in [1, 2] {
+            let dir = create_fixture(version, 0);
+            let db = Database::new(dir.path().to_path_buf()).unwrap();
+            assert_fixture_readable(&db);
+            drop(db);
+            assert_eq!(stored_version(dir.path()), latest_version());
+        }
+    }
+
+    #[test]
+    fn reopening_latest_database_is_a_no_op() {
+        let dir = create_fixture(MIGRATIONS.len(), latest_version());
+        drop(Database::new(dir.path().to_path_buf()).unwrap());
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        assert_fixture_readable(&db);
+    }
+
+    #[test]
+    fn rejects_newer_database() {
+        let dir = create_fixture(MIGRATIONS.len(), latest_version() + 1);
+        let error = Database::open(&dir.path().join("crontab.db")).err().expect("应拒绝更高版本的数据库");
+        assert!(matches!(error, MigrationError::UnsupportedVersion { .. }));
+    }
+
+    #[test]
+    fn corrupt_rows_are_skipped_reported_and_quarantined() {
+        let dir = create_fixture(MIGRATIONS.len(), latest_version());
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        db.writer()
+            .execute_batch(
+                "INSERT INTO job_history (id, job_id, execution_time, status, output)
+                 VALUES (NULL, 'job-1', '2024-01-02T04:00:00+00:00', 'Exploded', 'bad');
+                 INSERT INTO job_history (id, job_id, execution_time, status, output, duration_ms, exit_code, stdout, stderr)
+                 VALUES ('run-2', 'job-1', 'yesterday', 'Success', 'bad', 1500, 3, 'out', 'err');",
+            )
+            .unwrap();
+
+        // 列表查询跳过无法解码的记录
+        assert_fixture_readable(&db);
+
+        let report = db.check().unwrap();
+        assert_eq!(report.corrupt_rows.len(), 2);
+        let null_id = report.corrupt_rows.iter().find(|row| row.id.is_none()).unwrap();
+        assert!(null_id.problems.contains(&DecodeError::Missing { column: "id".to_string() }));
+        assert!(null_id.problems.contains(&DecodeError::UnknownStatus("Exploded".to_string())));
+        let bad_time = report.corrupt_rows.iter().find(|row| row.id.as_deref() == Some("run-2")).unwrap();
+        assert!(matches!(bad_time.problems[..], [DecodeError::InvalidExecutionTime { .. }]));
+
+        let report = db.repair().unwrap();
+        assert_eq!(report.quarantined, 2);
+        assert!(db.check().unwrap().corrupt_rows.is_empty());
+        let quarantined: i64 = db.writer()
+            .query_row("SELECT COUNT(*) FROM job_history_quarantine", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(quarantined, 2);
+        let kept: (i64, i64, String, String) = db.writer()
+            .query_row(
+                "SELECT duration_ms, exit_code, stdout, stderr FROM job_history_quarantine WHERE id = 'run-2'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(kept, (1500, 3, "out".to_string(), "err".to_string()));
+        assert_fixture_readable(&db);
+    }
+
+    #[test]
+    fn execution_times_are_normalized_and_filtered_as_strings() {
+        // v14 的记录可能带有时区偏移，迁移后统一为 UTC
+        let dir = create_fixture(14, 14);
+        {
+            let conn = Connection::open(dir.path().join("crontab.db")).unwrap();
+            conn.execute(
+                "INSERT INTO job_history (id, job_id, execution_time, status, output)
+                 VALUES ('run-2', 'job-1', '2024-01-02T12:00:00.5+08:00', 'Failed', 'boom')",
+                [],
+            )
+            .unwrap();
+        }
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let stored: Vec<String> = db.writer()
+            .prepare("SELECT execution_time FROM job_history ORDER BY execution_time")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(stored, ["2024-01-02T03:04:05.000Z", "2024-01-02T04:00:00.500Z"]);
+
+        let time = |value: &str| DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc);
+        let page = db.query_history(&HistoryQuery {
+            job_id: Some("job-1".to_string()),
+            from: Some(time("2024-01-02T03:04:05Z")),
+            to: Some(time("2024-01-02T04:00:00.500Z")),
+            ..HistoryQuery::default()
+        }).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].id, "run-1");
+
+        let page = db.query_history(&HistoryQuery {
+            status: Some(JobStatus::Failed),
+            search: Some("boo".to_string()),
+            include_output: true,
+            ..HistoryQuery::default()
+        }).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].output.as_deref(), Some("boom"));
+
+        let hits = db.search_logs(&LogSearchQuery {
+            text: "boom".to_string(),
+            job_id: Some("job-1".to_string()),
+            ..LogSearchQuery::default()
+        }).unwrap();
+        assert_eq!(hits.total, 1);
+        assert_eq!(hits.items[0].snippet, "[[boom]]");
+    }
+
+    #[test]
+    fn muted_job_ids_move_onto_jobs() {
+        let dir = create_fixture(16, 16);
+        {
+            let conn = Connection::open(dir.path().join("crontab.db")).unwrap();
+            conn.execute(
+                "INSERT INTO app_settings (key, value)
+                 VALUES ('desktop_notifications', '{\"enabled\":true,\"muted_job_ids\":[\"job-1\",\"deleted\"]}')",
+                [],
+            )
+            .unwrap();
+        }
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        assert!(db.get_job("job-1").unwrap().unwrap().notifications_muted);
+
+        assert!(db.set_job_notifications_muted("job-1", false).unwrap());
+        assert!(!db.get_job("job-1").unwrap().unwrap().notifications_muted);
+        assert!(!db.set_job_notifications_muted("deleted", true).unwrap());
+    }
+
+    #[test]
+    fn failures_after_are_limited_and_keep_undecodable_seqs() {
+        let dir = create_fixture(MIGRATIONS.len(), latest_version());
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let after = db.latest_history_seq().unwrap();
+        db.writer()
+            .execute_batch(
+                "INSERT INTO job_history (id, job_id, execution_time, status, output)
+                 VALUES ('run-2', 'job-1', 'yesterday', 'Failed', 'bad');
+                 INSERT INTO job_history (id, job_id, execution_time, status, output)
+                 VALUES ('run-3', 'job-1', '2024-01-02T05:00:00.000Z', 'Failed', 'boom');
+                 INSERT INTO job_history (id, job_id, execution_time, status, output)
+                 VALUES ('run-4', 'job-1', '2024-01-02T06:00:00.000Z', 'Failed', 'boom');",
+            )
+            .unwrap();
+
+        let failures = db.get_failures_after(after, 2).unwrap();
+        assert_eq!(failures.len(), 2);
+        assert!(failures[0].1.is_none());
+        assert_eq!(failures[1].1.as_ref().unwrap().id, "run-3");
+
+        let rest = db.get_failures_after(failures[1].0, 2).unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].1.as_ref().unwrap().id, "run-4");
+    }
+
+    #[test]
+    fn aggregates_for_one_job_match_the_full_listing() {
+        let dir = create_fixture(MIGRATIONS.len(), latest_version());
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        {
+            let conn = db.writer();
+            conn.execute(
+                "INSERT INTO cron_jobs (id, schedule, command, is_active) VALUES ('job-2', '* * * * *', 'true', 1)",
+                [],
+            )
+            .unwrap();
+            for i in 1..=20 {
+                for (job_id, scale) in [("job-1", 10), ("job-2", 1000)] {
+                    conn.execute(
+                        "INSERT INTO job_history (id, job_id, execution_time, status, output, duration_ms)
+                         VALUES (?1, ?2, '2024-01-02T05:00:00.000Z', 'Success', '', ?3)",
+                        params![format!("{}-{}", job_id, i), job_id, i * scale],
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        let all = db.get_job_aggregates(None).unwrap();
+        let one = db.get_job_aggregates(Some("job-1")).unwrap();
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0].p95_duration_ms, Some(190));
+        let listed = all.iter().find(|aggregate| aggregate.job_id == "job-1").unwrap();
+        assert_eq!(listed.p95_duration_ms, one[0].p95_duration_ms);
+        assert_eq!(listed.total_runs, one[0].total_runs);
+    }
+
+    #[test]
+    fn repair_rebuilds_the_log_index_only_when_it_is_out_of_sync() {
+        let dir = create_fixture(MIGRATIONS.len() - 1, latest_version() - 1);
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let report = db.check().unwrap();
+        assert!(report.corrupt_rows.is_empty());
+        assert_eq!(report.fts_mismatches, 0);
+
+        let total_changes = || -> i64 { db.writer().query_row("SELECT total_changes()", [], |row| row.get(0)).unwrap() };
+        let changes = total_changes();
+        assert_eq!(db.repair().unwrap().quarantined, 0);
+        assert_eq!(total_changes(), changes);
+
+        let seq = db.latest_history_seq().unwrap();
+        db.writer()
+            .execute(
+                "INSERT INTO job_history_fts (job_history_fts, rowid, output) VALUES ('delete', ?1, 'hi')",
+                params![seq],
+            )
+            .unwrap();
+        assert_eq!(db.check().unwrap().fts_mismatches, 1);
+        assert_eq!(db.repair().unwrap().fts_mismatches, 1);
+        assert_eq!(db.check().unwrap().fts_mismatches, 0);
+    }
+}
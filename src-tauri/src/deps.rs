@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use crate::{CronJob, Error, TriggerCondition};
+
+#[derive(Debug, Serialize, Clone)]
+pub struct GraphNode {
+    pub id: String,
+    pub name: Option<String>,
+    pub schedule: String,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct GraphEdge {
+    pub upstream_id: String,
+    pub downstream_id: String,
+    pub condition: TriggerCondition,
+}
+
+/// 任务依赖图，供前端绘制
+#[derive(Debug, Serialize, Clone)]
+pub struct DependencyGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+pub fn build_graph(jobs: &[CronJob]) -> DependencyGraph {
+    let nodes = jobs
+        .iter()
+        .map(|job| GraphNode {
+            id: job.id.clone(),
+            name: job.name.clone(),
+            schedule: job.schedule.clone(),
+            is_active: job.is_active,
+        })
+        .collect();
+
+    let edges = jobs
+        .iter()
+        .flat_map(|job| {
+            job.depends_on.iter().map(move |dependency| GraphEdge {
+                upstream_id: dependency.upstream_id.clone(),
+                downstream_id: job.id.clone(),
+                condition: dependency.condition.clone(),
+            })
+        })
+        .collect();
+
+    DependencyGraph { nodes, edges }
+}
+
+fn display_name(jobs: &HashMap<&str, &CronJob>, id: &str) -> String {
+    jobs.get(id)
+        .and_then(|job| job.name.clone())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// 保存任务前检查依赖：上游任务必须存在、不能依赖自己、不能形成环。
+/// `jobs` 为数据库中现有的任务，其中与 `job` 同 ID 的任务以 `job` 为准
+pub fn validate_dependencies(jobs: &[CronJob], job: &CronJob) -> Result<(), Error> {
+    let mut by_id: HashMap<&str, &CronJob> = jobs.iter().map(|j| (j.id.as_str(), j)).collect();
+    by_id.insert(job.id.as_str(), job);
+
+    for dependency in &job.depends_on {
+        if dependency.upstream_id == job.id {
+            return Err(Error::InvalidDependency("任务不能依赖自己".to_string()));
+        }
+        if !by_id.contains_key(dependency.upstream_id.as_str()) {
+            return Err(Error::InvalidDependency(format!("上游任务 {} 不存在", dependency.upstream_id)));
+        }
+    }
+
+    if let Some(cycle) = find_cycle(&by_id, &job.id) {
+        let path: Vec<String> = cycle.iter().map(|id| display_name(&by_id, id)).collect();
+        return Err(Error::InvalidDependency(format!("依赖形成环: {}", path.join(" -> "))));
+    }
+
+    Ok(())
+}
+
+/// 沿上游方向深度优先搜索，返回从 `start` 出发又回到 `start` 的路径
+fn find_cycle(jobs: &HashMap<&str, &CronJob>, start: &str) -> Option<Vec<String>> {
+    fn visit(
+        jobs: &HashMap<&str, &CronJob>,
+        start: &str,
+        current: &str,
+        path: &mut Vec<String>,
+        visited: &mut HashSet<String>,
+    ) -> bool {
+        let Some(job) = jobs.get(current) else {
+            return false;
+        };
+        for dependency in &job.depends_on {
+            let upstream = dependency.upstream_id.as_str();
+            if upstream == start {
+                path.push(upstream.to_string());
+                return true;
+            }
+            if visited.insert(upstream.to_string()) {
+                path.push(upstream.to_string());
+                if visit(jobs, start, upstream, path, visited) {
+                    return true;
+                }
+                path.pop();
+            }
+        }
+        false
+    }
+
+    let mut path = vec![start.to_string()];
+    let mut visited = HashSet::new();
+    if visit(jobs, start, start, &mut path, &mut visited) {
+        // 路径沿上游方向，反转后按执行顺序展示
+        path.reverse();
+        Some(path)
+    } else {
+        None
+    }
+}
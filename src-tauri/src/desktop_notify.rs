@@ -0,0 +1,152 @@
+//! 任务执行失败时的系统通知
+//!
+//! 后台线程轮询 `job_history`，发现 runner 新写入的失败记录时通过 Tauri 的通知接口提醒用户。
+//! 界面中手动执行的结果会直接显示，不再发送通知。
+
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use tauri::api::notification::Notification;
+use crontab_ui::db::Database;
+use crontab_ui::{Error, JobHistory, RunTrigger};
+
+/// 系统通知设置在 `app_settings` 中的键
+pub const SETTINGS_KEY: &str = "desktop_notifications";
+
+/// 检查新失败记录的轮询间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 每次轮询最多处理的失败记录数，积压的记录在之后的轮询中继续处理
+const BATCH_SIZE: u32 = 20;
+
+/// 同一条记录发送通知的最多尝试次数，超过后跳过，避免一条记录阻塞之后的通知
+const MAX_ATTEMPTS: u32 = 3;
+
+/// 免打扰时段，按本地时间计算，`start` 晚于 `end` 时跨越午夜
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// 系统通知设置。单个任务的静音保存在任务上（`CronJob::notifications_muted`）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DesktopNotificationSettings {
+    pub enabled: bool,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl Default for DesktopNotificationSettings {
+    fn default() -> Self {
+        DesktopNotificationSettings {
+            enabled: true,
+            quiet_hours: None,
+        }
+    }
+}
+
+impl DesktopNotificationSettings {
+    /// 当前是否应该为这次失败发送通知
+    fn should_notify(&self, history: &JobHistory) -> bool {
+        self.enabled
+            && history.trigger != RunTrigger::Manual
+            && !self.quiet_hours.is_some_and(|quiet| quiet.contains(Local::now().time()))
+    }
+}
+
+pub fn load_settings(db: &Database) -> Result<DesktopNotificationSettings, Error> {
+    let settings = db.get_setting(SETTINGS_KEY)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    Ok(settings.unwrap_or_default())
+}
+
+pub fn save_settings(db: &Database, settings: &DesktopNotificationSettings) -> Result<(), Error> {
+    db.set_setting(SETTINGS_KEY, settings)
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
+
+/// 为一次失败发送系统通知，静音的任务与已删除的任务不发送
+fn show_failure(app: &tauri::AppHandle, db: &Database, history: &JobHistory) -> Result<(), Error> {
+    let Some(job) = db.get_job(&history.job_id).map_err(|e| Error::DatabaseError(e.to_string()))? else {
+        return Ok(());
+    };
+    if job.notifications_muted {
+        return Ok(());
+    }
+    let name = job.name.unwrap_or_else(|| history.job_id.clone());
+    let body = match history.exit_code {
+        Some(code) => format!("退出码 {}，{}", code, history.execution_time.with_timezone(&Local).format("%H:%M:%S")),
+        None => format!("执行失败，{}", history.execution_time.with_timezone(&Local).format("%H:%M:%S")),
+    };
+
+    Notification::new(&app.config().tauri.bundle.identifier)
+        .title(format!("任务 {} 执行失败", name))
+        .body(body)
+        .show()
+        .map_err(|e| Error::NotificationError(e.to_string()))
+}
+
+/// 在后台轮询新的失败记录。启动前已有的记录不再通知，免打扰时段内的失败直接跳过。
+/// 已处理位置只在通知发送后前移，发送失败的记录在下次轮询时重试
+pub fn spawn_failure_watcher(app: tauri::AppHandle, db: Arc<Database>) {
+    std::thread::spawn(move || {
+        let mut last_seq = match db.latest_history_seq() {
+            Ok(seq) => seq,
+            Err(e) => {
+                eprintln!("Failed to start failure watcher: {}", e);
+                return;
+            }
+        };
+        // 当前记录已失败的发送次数
+        let mut attempts = 0;
+
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let settings = match load_settings(&db) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    eprintln!("Failed to load notification settings: {}", e);
+                    continue;
+                }
+            };
+            let failures = match db.get_failures_after(last_seq, BATCH_SIZE) {
+                Ok(failures) => failures,
+                Err(e) => {
+                    eprintln!("Failed to check failed runs: {}", e);
+                    continue;
+                }
+            };
+
+            for (seq, history) in failures {
+                match history {
+                    Some(history) if settings.should_notify(&history) => {
+                        if let Err(e) = show_failure(&app, &db, &history) {
+                            attempts += 1;
+                            eprintln!("Failed to show notification for run {} (attempt {}): {}", history.id, attempts, e);
+                            if attempts < MAX_ATTEMPTS {
+                                break;
+                            }
+                        }
+                    }
+                    Some(_) => {}
+                    None => eprintln!("Skipped undecodable run at seq {}, see check_database", seq),
+                }
+                last_seq = seq;
+                attempts = 0;
+            }
+        }
+    });
+}
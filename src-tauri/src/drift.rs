@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::crontab::{self, CrontabEntry};
+use crate::db::Database;
+use crate::{CronJob, Error};
+
+/// 数据库与 crontab 不一致的类型
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub enum DriftKind {
+    /// crontab 中的条目被手动修改，执行计划、名称或命令与数据库不一致
+    Modified,
+    /// 启用中的任务在 crontab 中没有条目
+    Missing,
+    /// 同一个任务 ID 在 crontab 中出现多次
+    Duplicated,
+    /// 数据库中已停用或只由上游任务触发的任务仍在 crontab 中
+    Inactive,
+    /// crontab 中的任务 ID 在数据库中不存在
+    Unknown,
+}
+
+/// 一个任务的不一致情况，同时给出数据库与 crontab 两侧的内容供用户对比
+#[derive(Debug, Serialize, Clone)]
+pub struct Drift {
+    pub job_id: String,
+    pub kind: DriftKind,
+    pub job: Option<CronJob>,
+    pub entries: Vec<CrontabEntry>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct DriftReport {
+    pub drifts: Vec<Drift>,
+}
+
+impl DriftReport {
+    pub fn is_empty(&self) -> bool {
+        self.drifts.is_empty()
+    }
+
+    pub fn find(&self, job_id: &str) -> Option<&Drift> {
+        self.drifts.iter().find(|drift| drift.job_id == job_id)
+    }
+}
+
+/// 处理不一致时保留哪一侧
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum DriftResolution {
+    /// 以数据库为准，重新生成 crontab 条目
+    KeepDatabase,
+    /// 以 crontab 为准，更新数据库中的任务
+    KeepCrontab,
+}
+
+fn normalize_schedule(schedule: &str) -> String {
+    schedule.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// crontab 条目与数据库中的任务是否一致
+fn entry_matches(entry: &CrontabEntry, job: &CronJob) -> bool {
+    normalize_schedule(&entry.schedule) == normalize_schedule(&job.schedule)
+        && entry.name.as_deref().unwrap_or("") == job.name.as_deref().unwrap_or("")
+        && entry.mailto == job.mailto
+        && entry.runs_job(job)
+}
+
+/// 比较 crontab 内容与数据库中的任务，找出两侧不一致的任务
+pub fn detect_drift(content: &str, jobs: &[CronJob]) -> DriftReport {
+    let mut entries_by_id: HashMap<&str, Vec<CrontabEntry>> = HashMap::new();
+    let mut entry_order: Vec<&str> = Vec::new();
+    let entries = crontab::parse_entries(content);
+    for entry in &entries {
+        if !entries_by_id.contains_key(entry.job_id.as_str()) {
+            entry_order.push(&entry.job_id);
+        }
+        entries_by_id.entry(&entry.job_id).or_default().push(entry.clone());
+    }
+
+    let mut drifts = Vec::new();
+
+    for job in jobs {
+        let entries = entries_by_id.remove(job.id.as_str()).unwrap_or_default();
+        let expected = job.is_active && job.is_scheduled();
+
+        let kind = match entries.as_slice() {
+            [] if expected => Some(DriftKind::Missing),
+            [] => None,
+            _ if !expected => Some(DriftKind::Inactive),
+            [entry] if !entry_matches(entry, job) => Some(DriftKind::Modified),
+            [_] => None,
+            _ => Some(DriftKind::Duplicated),
+        };
+
+        if let Some(kind) = kind {
+            drifts.push(Drift {
+                job_id: job.id.clone(),
+                kind,
+                job: Some(job.clone()),
+                entries,
+            });
+        }
+    }
+
+    for job_id in entry_order {
+        if let Some(entries) = entries_by_id.remove(job_id) {
+            drifts.push(Drift {
+                job_id: job_id.to_string(),
+                kind: DriftKind::Unknown,
+                job: None,
+                entries,
+            });
+        }
+    }
+
+    DriftReport { drifts }
+}
+
+/// 用 crontab 条目的内容更新任务，runner 调用中不包含命令时保留原命令
+fn apply_entry(job: &mut CronJob, entry: &CrontabEntry) {
+    job.schedule = entry.schedule.clone();
+    job.name = entry.name.clone();
+    job.mailto = entry.mailto.clone();
+    if let Some(command) = entry.command() {
+        job.command = command;
+    }
+}
+
+/// 按用户的选择处理一个任务的不一致。以 crontab 为准时更新数据库，
+/// 重复的条目取第一条；之后由调用方以数据库为准重新生成该任务的 crontab 条目
+pub fn resolve_drift(db: &Database, drift: &Drift, resolution: DriftResolution) -> Result<(), Error> {
+    if resolution == DriftResolution::KeepDatabase {
+        return Ok(());
+    }
+
+    match (&drift.job, drift.entries.first()) {
+        (Some(job), Some(entry)) => {
+            let mut job = job.clone();
+            apply_entry(&mut job, entry);
+            job.is_active = true;
+            job.validate()?;
+            db.update_job(&job)
+                .map_err(|e| Error::DatabaseError(e.to_string()))
+        }
+        (Some(job), None) => {
+            // crontab 中的条目已被删除，停用任务
+            let mut job = job.clone();
+            job.is_active = false;
+            db.update_job(&job)
+                .map_err(|e| Error::DatabaseError(e.to_string()))
+        }
+        (None, Some(entry)) => {
+            let command = entry.command().ok_or_else(|| {
+                Error::InvalidCommand(format!("任务 {} 的条目不包含命令，无法导入", drift.job_id))
+            })?;
+            let job = CronJob {
+                id: drift.job_id.clone(),
+                name: entry.name.clone(),
+                schedule: entry.schedule.clone(),
+                command,
+                is_active: true,
+                mailto: entry.mailto.clone(),
+                ..CronJob::default()
+            };
+            job.validate()?;
+            db.add_job(&job)
+                .map_err(|e| Error::DatabaseError(e.to_string()))
+        }
+        (None, None) => Ok(()),
+    }
+}
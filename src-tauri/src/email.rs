@@ -0,0 +1,297 @@
+use std::time::Duration;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use crate::notify::WebhookPayload;
+use crate::Error;
+
+/// SMTP 设置在 `app_settings` 中的键
+pub const SETTINGS_KEY: &str = "smtp";
+
+/// 连接与发送的超时时间
+const SMTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub const DEFAULT_SUBJECT_TEMPLATE: &str = "[crontab-ui] 任务 {{job_name}} 执行失败";
+
+pub const DEFAULT_BODY_TEMPLATE: &str = "任务：{{job_name}} ({{job_id}})
+状态：{{status}}
+退出码：{{exit_code}}
+耗时：{{duration_ms}} ms
+执行时间：{{execution_time}}
+触发方式：{{trigger}}
+
+最后 50 行日志：
+{{log_tail}}
+";
+
+/// 与 SMTP 服务器之间的加密方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum SmtpTls {
+    /// 明文连接，只用于本机或可信网络中的中继
+    None,
+    /// 明文连接后通过 STARTTLS 升级，服务器不支持时报错
+    #[default]
+    StartTls,
+    /// 直接建立 TLS 连接（SMTPS）
+    Tls,
+}
+
+/// 邮件通知设置，任务配置了收件人且执行失败时发送
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SmtpSettings {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    /// 只在保存时提交，读取设置时不返回（见 `SmtpSettingsView`）
+    #[serde(default)]
+    pub password: Option<String>,
+    /// 发件人，例如 `Crontab UI <cron@example.com>`
+    pub from: String,
+    /// 主题与正文模板，`{{job_name}}`、`{{status}}`、`{{log_tail}}` 等占位符替换为执行结果；为空时使用默认模板
+    #[serde(default)]
+    pub subject_template: Option<String>,
+    #[serde(default)]
+    pub body_template: Option<String>,
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        SmtpSettings {
+            enabled: false,
+            host: String::new(),
+            port: 587,
+            tls: SmtpTls::default(),
+            username: None,
+            password: None,
+            from: String::new(),
+            subject_template: None,
+            body_template: None,
+        }
+    }
+}
+
+impl SmtpSettings {
+    /// 密码只写不读：提交的设置中没有密码时沿用已保存的密码，密码为空字符串时清除
+    pub fn with_stored_password(mut self, stored: Option<&SmtpSettings>) -> Self {
+        match self.password.as_deref() {
+            None => self.password = stored.and_then(|stored| stored.password.clone()),
+            Some("") => self.password = None,
+            Some(_) => {}
+        }
+        self
+    }
+}
+
+/// 返回给界面的 SMTP 设置，不包含密码，只标明是否已保存密码
+#[derive(Debug, Serialize, Clone)]
+pub struct SmtpSettingsView {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password_set: bool,
+    pub from: String,
+    pub subject_template: Option<String>,
+    pub body_template: Option<String>,
+}
+
+impl From<SmtpSettings> for SmtpSettingsView {
+    fn from(settings: SmtpSettings) -> Self {
+        SmtpSettingsView {
+            enabled: settings.enabled,
+            host: settings.host,
+            port: settings.port,
+            tls: settings.tls,
+            username: settings.username,
+            password_set: settings.password.is_some_and(|password| !password.is_empty()),
+            from: settings.from,
+            subject_template: settings.subject_template,
+            body_template: settings.body_template,
+        }
+    }
+}
+
+/// 替换模板中的 `{{name}}` 占位符，未知的占位符保留原样
+pub fn render_template(template: &str, payload: &WebhookPayload) -> String {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let values = [
+        ("job_id", payload.job_id.clone()),
+        ("job_name", payload.job_name.clone().unwrap_or_else(|| payload.job_id.clone())),
+        ("status", format!("{:?}", payload.status)),
+        ("exit_code", optional(payload.exit_code.map(|code| code.to_string()))),
+        ("duration_ms", optional(payload.duration_ms.map(|ms| ms.to_string()))),
+        ("execution_time", payload.execution_time.to_rfc3339()),
+        ("trigger", format!("{:?}", payload.trigger)),
+        ("log_tail", payload.log_tail.clone()),
+    ];
+
+    values.iter().fold(template.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{{{}}}}}", name), value)
+    })
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, Error> {
+    address
+        .trim()
+        .parse()
+        .map_err(|e| Error::InvalidNotification(format!("无效的邮件地址 {}: {}", address, e)))
+}
+
+/// 检查收件人地址格式
+pub fn validate_recipients(recipients: &[String]) -> Result<(), Error> {
+    for recipient in recipients {
+        parse_mailbox(recipient)?;
+    }
+    Ok(())
+}
+
+fn transport(settings: &SmtpSettings) -> Result<SmtpTransport, Error> {
+    let smtp_error = |e: lettre::transport::smtp::Error| Error::NotificationError(format!("SMTP 配置错误: {}", e));
+    let builder = match settings.tls {
+        SmtpTls::None => SmtpTransport::builder_dangerous(settings.host.trim()),
+        SmtpTls::StartTls => SmtpTransport::starttls_relay(settings.host.trim()).map_err(smtp_error)?,
+        SmtpTls::Tls => SmtpTransport::relay(settings.host.trim()).map_err(smtp_error)?,
+    };
+    let mut builder = builder.port(settings.port).timeout(Some(SMTP_TIMEOUT));
+
+    if let Some(username) = settings.username.as_deref().filter(|username| !username.is_empty()) {
+        let password = settings.password.clone().unwrap_or_default();
+        builder = builder.credentials(Credentials::new(username.to_string(), password));
+    }
+
+    Ok(builder.build())
+}
+
+/// 按模板生成邮件并发送给所有收件人
+pub fn send_email(settings: &SmtpSettings, recipients: &[String], payload: &WebhookPayload) -> Result<(), Error> {
+    if settings.host.trim().is_empty() {
+        return Err(Error::InvalidNotification("未设置 SMTP 服务器".to_string()));
+    }
+
+    let subject_template = settings.subject_template.as_deref().unwrap_or(DEFAULT_SUBJECT_TEMPLATE);
+    let body_template = settings.body_template.as_deref().unwrap_or(DEFAULT_BODY_TEMPLATE);
+
+    let mut builder = Message::builder()
+        .from(parse_mailbox(&settings.from)?)
+        .subject(render_template(subject_template, payload))
+        .header(ContentType::TEXT_PLAIN);
+    for recipient in recipients {
+        builder = builder.to(parse_mailbox(recipient)?);
+    }
+    let message = builder
+        .body(render_template(body_template, payload))
+        .map_err(|e| Error::NotificationError(format!("生成邮件失败: {}", e)))?;
+
+    transport(settings)?
+        .send(&message)
+        .map_err(|e| Error::NotificationError(format!("发送邮件失败: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+    use crate::{notify, CronJob};
+
+    /// 只实现发送流程所需命令的本地 SMTP 服务，返回收到的全部命令与邮件内容
+    fn stub_smtp() -> (u16, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut transcript = String::new();
+            writer.write_all(b"220 localhost ESMTP\r\n").unwrap();
+
+            let mut in_data = false;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                transcript.push_str(&line);
+                let reply: &[u8] = if in_data {
+                    if line == ".\r\n" {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    } else {
+                        b""
+                    }
+                } else {
+                    match line.split_whitespace().next().unwrap_or_default().to_ascii_uppercase().as_str() {
+                        "EHLO" => b"250-localhost\r\n250 AUTH PLAIN LOGIN\r\n",
+                        "AUTH" => b"235 authenticated\r\n",
+                        "DATA" => {
+                            in_data = true;
+                            b"354 go ahead\r\n"
+                        }
+                        "QUIT" => {
+                            writer.write_all(b"221 bye\r\n").unwrap();
+                            break;
+                        }
+                        _ => b"250 ok\r\n",
+                    }
+                };
+                writer.write_all(reply).unwrap();
+                line.clear();
+            }
+            transcript
+        });
+        (port, handle)
+    }
+
+    fn stored_settings(port: u16) -> SmtpSettings {
+        SmtpSettings {
+            enabled: true,
+            host: "127.0.0.1".to_string(),
+            port,
+            tls: SmtpTls::None,
+            username: Some("cron".to_string()),
+            password: Some("secret".to_string()),
+            from: "cron@example.com".to_string(),
+            subject_template: Some("job {{job_id}} {{status}}".to_string()),
+            body_template: Some("tail: {{log_tail}}".to_string()),
+        }
+    }
+
+    #[test]
+    fn password_is_write_only() {
+        let stored = stored_settings(25);
+        let view = serde_json::to_value(SmtpSettingsView::from(stored.clone())).unwrap();
+        assert!(view.get("password").is_none());
+        assert_eq!(view["password_set"], true);
+
+        let submitted = SmtpSettings { password: None, ..stored.clone() };
+        assert_eq!(submitted.with_stored_password(Some(&stored)).password.as_deref(), Some("secret"));
+        let cleared = SmtpSettings { password: Some(String::new()), ..stored.clone() };
+        assert_eq!(cleared.with_stored_password(Some(&stored)).password, None);
+        let changed = SmtpSettings { password: Some("new".to_string()), ..stored.clone() };
+        assert_eq!(changed.with_stored_password(Some(&stored)).password.as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn sends_through_a_local_smtp_server_with_the_stored_password() {
+        let (port, server) = stub_smtp();
+        let stored = stored_settings(port);
+        let settings = SmtpSettings { password: None, ..stored.clone() }.with_stored_password(Some(&stored));
+
+        let job = CronJob { id: "job-1".to_string(), ..CronJob::default() };
+        let mut payload = WebhookPayload::new(&job, &notify::sample_history(&job.id), None);
+        payload.log_tail = "disk full".to_string();
+        send_email(&settings, &["ops@example.com".to_string()], &payload).unwrap();
+
+        let transcript = server.join().unwrap();
+        // `\0cron\0secret` 的 base64
+        assert!(transcript.contains("AUTH PLAIN AGNyb24Ac2VjcmV0"), "{}", transcript);
+        assert!(transcript.contains("MAIL FROM:<cron@example.com>"));
+        assert!(transcript.contains("RCPT TO:<ops@example.com>"));
+        assert!(transcript.contains("Subject: job job-1 Success"));
+        assert!(transcript.contains("tail: disk full"));
+    }
+}
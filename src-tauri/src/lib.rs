@@ -0,0 +1,302 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+
+pub mod capture;
+pub mod criteria;
+pub mod crontab;
+pub mod db;
+pub mod deps;
+pub mod drift;
+pub mod email;
+pub mod logs;
+pub mod missed;
+pub mod notify;
+pub mod python;
+pub mod runner;
+pub mod schedule;
+pub mod transfer;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CronJob {
+    pub id: String,
+    pub name: Option<String>,
+    pub schedule: String,
+    pub command: String,
+    pub is_active: bool,
+    /// 错过计划执行后（电脑休眠、关机）是否在下次启动时补执行一次
+    #[serde(default)]
+    pub catch_up: bool,
+    /// 补执行允许的最大延迟，超过后放弃补执行；为空时使用默认值
+    #[serde(default)]
+    pub max_lateness_minutes: Option<u32>,
+    /// 上游任务执行完成后按条件触发本任务
+    #[serde(default)]
+    pub depends_on: Vec<JobDependency>,
+    /// 用于分组筛选与批量操作的标签
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// 执行完成后按规则发送的 Webhook 通知
+    #[serde(default)]
+    pub webhooks: Vec<WebhookRule>,
+    /// 执行失败时接收邮件通知的地址
+    #[serde(default)]
+    pub email_recipients: Vec<String>,
+    /// 单独为该任务设置的 `MAILTO`，设置后 cron 会把 runner 打印的输出发送到该地址
+    #[serde(default)]
+    pub mailto: Option<String>,
+    /// 判断执行是否成功的规则，未设置时只按退出码 0 判断
+    #[serde(default)]
+    pub success_rules: Vec<SuccessRule>,
+    /// 执行失败时不发送系统通知。只通过 `Database::set_job_notifications_muted` 修改，保存任务时不会覆盖
+    #[serde(default)]
+    pub notifications_muted: bool,
+}
+
+impl CronJob {
+    /// 是否有自己的执行计划；没有计划的任务只能由上游任务触发，不写入 crontab
+    pub fn is_scheduled(&self) -> bool {
+        !self.schedule.trim().is_empty()
+    }
+
+    /// 检查命令与执行计划。只由上游任务触发的任务可以不设置执行计划，其余任务的执行计划必须有效
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.command.trim().is_empty() {
+            return Err(Error::InvalidCommand("Command cannot be empty".to_string()));
+        }
+        // 只拦截删除根目录这类极其危险的命令
+        if self.command.contains("rm -rf /") {
+            return Err(Error::InvalidCommand("Extremely dangerous command detected".to_string()));
+        }
+        if self.is_scheduled() || self.depends_on.is_empty() {
+            if !self.is_scheduled() {
+                return Err(Error::InvalidCronExpression("Cron expression cannot be empty".into()));
+            }
+            schedule::parse_schedule(&self.schedule)?;
+        }
+        self.validate_notifications()?;
+        criteria::validate_rules(&self.success_rules)
+    }
+
+    /// 检查 Webhook 地址、连续失败次数、邮件收件人与 `MAILTO`
+    pub fn validate_notifications(&self) -> Result<(), Error> {
+        for rule in &self.webhooks {
+            let url = rule.url.trim();
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(Error::InvalidNotification(format!("Webhook 地址必须以 http:// 或 https:// 开头: {}", url)));
+            }
+            if rule.event == NotifyEvent::OnConsecutiveFailures(0) {
+                return Err(Error::InvalidNotification("连续失败次数必须大于 0".to_string()));
+            }
+        }
+        email::validate_recipients(&self.email_recipients)?;
+
+        if let Some(mailto) = &self.mailto {
+            let addresses: Vec<String> = mailto.split(',').map(str::to_string).collect();
+            email::validate_recipients(&addresses)?;
+        }
+        Ok(())
+    }
+}
+
+/// 根据退出码与输出判断执行结果的规则
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum SuccessRule {
+    /// 视为成功的退出码，设置后 0 也需要单独列出
+    ExitCode(i32),
+    /// 输出中匹配到该正则时判为失败
+    FailurePattern(String),
+    /// 输出中必须匹配到该正则，否则判为失败
+    RequiredPattern(String),
+}
+
+/// 依赖的触发条件
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum TriggerCondition {
+    OnSuccess,
+    OnFailure,
+}
+
+/// 一条依赖：`upstream_id` 执行结果满足 `condition` 时触发下游任务
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JobDependency {
+    pub upstream_id: String,
+    pub condition: TriggerCondition,
+}
+
+/// 发送通知的时机
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum NotifyEvent {
+    OnFailure,
+    OnSuccess,
+    /// 失败后的第一次成功
+    OnRecovery,
+    /// 连续失败达到指定次数时发送一次
+    OnConsecutiveFailures(u32),
+}
+
+/// 一条 Webhook 通知规则：执行结果满足 `event` 时向 `url` POST 执行结果
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WebhookRule {
+    pub url: String,
+    pub event: NotifyEvent,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobHistory {
+    pub id: String,
+    pub job_id: String,
+    pub execution_time: DateTime<Utc>,
+    pub status: JobStatus,
+    pub output: String,
+    pub duration_ms: Option<i64>,
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub trigger: RunTrigger,
+    /// 决定本次执行结果的规则，按默认规则（退出码 0 为成功）判断时为空
+    #[serde(default)]
+    pub matched_rule: Option<String>,
+    /// 分开采集时的 stdout 与 stderr，合并采集时为空
+    #[serde(default)]
+    pub stdout: Option<String>,
+    #[serde(default)]
+    pub stderr: Option<String>,
+    /// 输出超出保存上限时省略的字节数
+    #[serde(default)]
+    pub omitted_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum JobStatus {
+    Success,
+    Failed,
+}
+
+/// 执行的触发方式
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub enum RunTrigger {
+    /// 由 crontab 按计划触发
+    #[default]
+    Schedule,
+    /// 错过计划执行后的补执行
+    CatchUp,
+    /// 用户手动触发
+    Manual,
+    /// 上游任务执行完成后触发
+    Dependency,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid cron expression: {0}")]
+    InvalidCronExpression(String),
+
+    #[error("Invalid command: {0}")]
+    InvalidCommand(String),
+
+    #[error("Failed to update crontab: {0}")]
+    CrontabError(String),
+
+    #[error("Job not found")]
+    JobNotFound,
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+
+    #[error("Permission error: {0}")]
+    PermissionError(String),
+
+    #[error("Python error: {0}")]
+    PythonError(String),
+
+    #[error("Invalid dependency: {0}")]
+    InvalidDependency(String),
+
+    #[error("Invalid import: {0}")]
+    InvalidImport(String),
+
+    #[error("Invalid notification: {0}")]
+    InvalidNotification(String),
+
+    #[error("Notification error: {0}")]
+    NotificationError(String),
+
+    #[error("Invalid success rule: {0}")]
+    InvalidSuccessRule(String),
+
+    #[error("Runner not available: {0}")]
+    RunnerUnavailable(String),
+
+    #[error("API server error: {0}")]
+    ApiServerError(String),
+}
+
+impl serde::Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.to_string().as_ref())
+    }
+}
+
+/// 应用数据目录，GUI 与 runner 使用同一位置的数据库
+pub fn data_dir() -> Option<PathBuf> {
+    tauri::api::path::app_data_dir(&tauri::Config::default())
+}
+
+/// 任务日志目录 `~/.crontab`
+pub fn log_dir() -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_default();
+    PathBuf::from(home).join(".crontab")
+}
+
+/// 测试使用的临时目录，离开作用域时删除
+#[cfg(test)]
+pub(crate) struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("crontab-ui-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub(crate) fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(schedule: &str, command: &str) -> CronJob {
+        CronJob {
+            id: "job-1".to_string(),
+            schedule: schedule.to_string(),
+            command: command.to_string(),
+            ..CronJob::default()
+        }
+    }
+
+    #[test]
+    fn validate_checks_schedule_and_command() {
+        assert!(job("*/5 * * * *", "echo hi").validate().is_ok());
+        assert!(matches!(job("not a schedule", "echo hi").validate(), Err(Error::InvalidCronExpression(_))));
+        assert!(matches!(job("", "echo hi").validate(), Err(Error::InvalidCronExpression(_))));
+        assert!(matches!(job("0 * * * *", "  ").validate(), Err(Error::InvalidCommand(_))));
+
+        for command in ["rm -rf /", "rm -rf /*", "cd /tmp && rm -rf /;"] {
+            assert!(matches!(job("0 * * * *", command).validate(), Err(Error::InvalidCommand(_))), "{}", command);
+        }
+    }
+}
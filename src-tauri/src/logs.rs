@@ -0,0 +1,638 @@
+//! 按位置分段读取任务日志，用于实时查看正在执行的任务输出与浏览大日志
+//!
+//! 每次执行的输出写入单独的日志 `~/.crontab/<job_id>/<开始时间>-<run_id>.log`，
+//! `run_id` 即执行记录的 id。执行期间文件名以 `.part` 结尾，结束后去掉该后缀，
+//! 同一任务的多次执行即使时间重叠也互不影响。每个任务只保留最近 [`KEEP_RUNS`] 次执行的日志。
+//!
+//! 调用方记住上次返回的 `run_id` 与 `next_offset`，之后从该位置继续读取同一次执行追加的内容；
+//! 该次执行的日志已不存在时改为读取最近一次执行，并通过 `reset` 告知调用方。
+//!
+//! 开启压缩后，执行结束且超过一定大小的日志压缩为 `.log.gz`，
+//! 读取时自动解压，位置与大小均按解压后的内容计算。
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use flate2::read::GzDecoder;
+use flate2::{Compression, GzBuilder};
+use serde::{Deserialize, Serialize};
+use crate::{log_dir, Error};
+
+/// 首次读取时最多返回的字节数，更早的内容需要按范围读取
+pub const DEFAULT_TAIL_BYTES: u64 = 64 * 1024;
+
+/// 单次读取最多返回的字节数
+pub const MAX_CHUNK_BYTES: u64 = 1024 * 1024;
+
+/// 每个任务保留的执行日志数，更早的日志在执行结束时删除
+pub const KEEP_RUNS: usize = 20;
+
+/// 日志设置在 `app_settings` 中的键
+pub const SETTINGS_KEY: &str = "logs";
+
+/// 执行中的日志后缀
+const RUNNING_SUFFIX: &str = ".log.part";
+const FINISHED_SUFFIX: &str = ".log";
+const COMPRESSED_SUFFIX: &str = ".log.gz";
+
+/// 日志文件名中开始时间的格式，按字符串排序即按时间排序
+const START_TIME_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+/// 压缩日志时在 gzip 头部扩展字段中记录原始大小所用的子字段标识
+const SIZE_SUBFIELD: [u8; 2] = *b"SZ";
+
+/// 日志压缩设置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LogSettings {
+    /// 执行结束后压缩日志
+    pub compress: bool,
+    /// 只压缩不小于该大小的日志
+    pub compress_min_bytes: u64,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        LogSettings {
+            compress: false,
+            compress_min_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// 一次执行的日志
+#[derive(Debug, Clone)]
+struct RunLog {
+    /// 早期版本的共用日志没有 `run_id`
+    run_id: Option<String>,
+    path: PathBuf,
+    /// 开始执行的时间，早期版本的共用日志没有记录
+    started: Option<DateTime<Utc>>,
+    finished: bool,
+    compressed: bool,
+}
+
+impl RunLog {
+    /// 按文件名识别日志，`<开始时间>-<run_id>` 加上执行中、已结束或已压缩的后缀
+    fn parse(path: PathBuf) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        let (stem, finished, compressed) = if let Some(stem) = name.strip_suffix(RUNNING_SUFFIX) {
+            (stem, false, false)
+        } else if let Some(stem) = name.strip_suffix(COMPRESSED_SUFFIX) {
+            (stem, true, true)
+        } else if let Some(stem) = name.strip_suffix(FINISHED_SUFFIX) {
+            (stem, true, false)
+        } else {
+            return None;
+        };
+        let (started, run_id) = stem.split_once('-')?;
+        let started = NaiveDateTime::parse_from_str(started, START_TIME_FORMAT).ok().map(|time| Utc.from_utc_datetime(&time));
+        Some(RunLog { run_id: Some(run_id.to_string()), path, started, finished, compressed })
+    }
+}
+
+/// 要读取的日志范围
+#[derive(Debug, Deserialize, Clone)]
+pub enum LogRange {
+    /// 开头的若干字节
+    Head(u64),
+    /// 末尾的若干字节，跳过开头不完整的一行
+    Tail(u64),
+    /// 从 `offset` 开始的 `length` 字节
+    Range { offset: u64, length: u64 },
+}
+
+/// 日志中的一段内容
+#[derive(Debug, Serialize, Clone)]
+pub struct LogChunk {
+    pub job_id: String,
+    /// 本段所属的执行，早期版本的共用日志为空
+    pub run_id: Option<String>,
+    /// 本段内容在文件中的起始位置
+    pub offset: u64,
+    /// 下次读取的起始位置
+    pub next_offset: u64,
+    /// 读取时日志的大小
+    pub size: u64,
+    /// 请求的执行日志已不存在，本段改为从最近一次执行的日志开头读取，调用方应清空已显示的内容
+    pub reset: bool,
+    /// 跳过了 `offset` 之前的内容
+    pub truncated: bool,
+    /// 该次执行已结束，读到 `size` 之后不会再有新内容
+    pub finished: bool,
+    pub data: String,
+}
+
+impl LogChunk {
+    fn empty(job_id: &str) -> Self {
+        LogChunk {
+            job_id: job_id.to_string(),
+            run_id: None,
+            offset: 0,
+            next_offset: 0,
+            size: 0,
+            reset: false,
+            truncated: false,
+            finished: false,
+            data: String::new(),
+        }
+    }
+}
+
+/// 日志文件的大小信息
+#[derive(Debug, Serialize, Clone)]
+pub struct LogInfo {
+    pub job_id: String,
+    pub run_id: Option<String>,
+    pub exists: bool,
+    /// 解压后的大小
+    pub size: u64,
+    /// 磁盘上占用的大小
+    pub stored_size: u64,
+    pub compressed: bool,
+    pub finished: bool,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+fn read_error(e: std::io::Error) -> Error {
+    Error::CrontabError(format!("读取日志失败: {}", e))
+}
+
+/// gzip 头部扩展字段，记录原始大小
+fn size_extra(size: u64) -> Vec<u8> {
+    let mut extra = SIZE_SUBFIELD.to_vec();
+    extra.extend_from_slice(&8u16.to_le_bytes());
+    extra.extend_from_slice(&size.to_le_bytes());
+    extra
+}
+
+/// 从 gzip 头部扩展字段的各个子字段中找出原始大小
+fn stored_size(mut extra: &[u8]) -> Option<u64> {
+    while extra.len() >= 4 {
+        let length = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let data = extra.get(4..4 + length)?;
+        if extra[..2] == SIZE_SUBFIELD && length == 8 {
+            return Some(u64::from_le_bytes(data.try_into().ok()?));
+        }
+        extra = &extra[4 + length..];
+    }
+    None
+}
+
+/// 打开压缩的日志，并返回解压后的大小：优先使用压缩时写入头部的原始大小。
+/// 早期压缩的日志没有该字段，而 gzip 末尾的 ISIZE 只是对 2^32 取模的值，只能完整解压一遍计数
+fn open_compressed(path: &Path) -> std::io::Result<(GzDecoder<BufReader<File>>, u64)> {
+    let decoder = GzDecoder::new(BufReader::new(File::open(path)?));
+    if let Some(size) = decoder.header().and_then(|header| header.extra()).and_then(stored_size) {
+        return Ok((decoder, size));
+    }
+    let size = std::io::copy(&mut GzDecoder::new(BufReader::new(File::open(path)?)), &mut std::io::sink())?;
+    Ok((decoder, size))
+}
+
+/// 打开的日志，`size` 为解压后的大小
+struct OpenedLog {
+    run: RunLog,
+    reader: Box<dyn Read>,
+    size: u64,
+}
+
+/// 读取最多 `max_bytes` 字节。末尾不完整的 UTF-8 字符留到下次读取，无效的字节按替换字符显示
+fn read_bytes(reader: impl Read, max_bytes: u64) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    reader.take(max_bytes).read_to_end(&mut bytes).map_err(read_error)?;
+
+    if let Err(e) = std::str::from_utf8(&bytes) {
+        if e.error_len().is_none() {
+            bytes.truncate(e.valid_up_to());
+        }
+    }
+    Ok(bytes)
+}
+
+fn chunk(job_id: &str, run: RunLog, size: u64, offset: u64, bytes: &[u8], reset: bool) -> LogChunk {
+    LogChunk {
+        job_id: job_id.to_string(),
+        run_id: run.run_id,
+        offset,
+        next_offset: offset + bytes.len() as u64,
+        size,
+        reset,
+        truncated: offset > 0,
+        finished: run.finished,
+        data: String::from_utf8_lossy(bytes).to_string(),
+    }
+}
+
+/// 按设置压缩执行结束的日志 `path`：先写入临时文件再改名，最后删除原文件，
+/// 过程中读取方始终能读到完整的日志。只处理已去掉 `.part` 后缀的日志，
+/// 删除的原文件只属于这一次执行，不会影响同一任务正在执行的其他日志
+fn compress_if_needed(path: &Path, settings: &LogSettings) -> Result<bool, Error> {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return Ok(false);
+    };
+    if !settings.compress || metadata.len() < settings.compress_min_bytes {
+        return Ok(false);
+    }
+
+    let write_error = |e: std::io::Error| Error::CrontabError(format!("压缩日志失败: {}", e));
+    let target = path.with_extension("log.gz");
+    let temp = path.with_extension("log.gz.tmp");
+
+    let mut input = File::open(path).map_err(read_error)?;
+    let encoder = GzBuilder::new().extra(size_extra(metadata.len()));
+    let mut encoder = encoder.write(File::create(&temp).map_err(write_error)?, Compression::default());
+    std::io::copy(&mut input, &mut encoder).map_err(write_error)?;
+    encoder.finish().map_err(write_error)?;
+
+    std::fs::rename(&temp, &target).map_err(write_error)?;
+    std::fs::remove_file(path).map_err(write_error)?;
+    Ok(true)
+}
+
+/// 任务日志的存放位置，默认为 [`log_dir`]
+#[derive(Debug, Clone)]
+pub struct LogStore {
+    root: PathBuf,
+}
+
+impl Default for LogStore {
+    fn default() -> Self {
+        LogStore::new(log_dir())
+    }
+}
+
+impl LogStore {
+    pub fn new(root: PathBuf) -> Self {
+        LogStore { root }
+    }
+
+    /// 任务的日志目录
+    fn job_log_dir(&self, job_id: &str) -> PathBuf {
+        self.root.join(job_id)
+    }
+
+    /// 早期版本所有执行共用的日志，没有按次保存的日志时读取
+    fn legacy_log_paths(&self, job_id: &str) -> [PathBuf; 2] {
+        [
+            self.root.join(format!("{}.log", job_id)),
+            self.root.join(format!("{}.log.gz", job_id)),
+        ]
+    }
+
+    /// 任务的所有执行日志，按开始时间排列
+    fn list_runs(&self, job_id: &str) -> std::io::Result<Vec<RunLog>> {
+        let entries = match std::fs::read_dir(self.job_log_dir(job_id)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut runs = Vec::new();
+        for entry in entries {
+            runs.extend(RunLog::parse(entry?.path()));
+        }
+        runs.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(runs)
+    }
+
+    /// 查找 `run_id` 指定的执行日志，未指定时查找最近一次执行的日志
+    fn find_run(&self, job_id: &str, run_id: Option<&str>) -> Result<Option<RunLog>, Error> {
+        let runs = self.list_runs(job_id).map_err(read_error)?;
+        if let Some(run_id) = run_id {
+            return Ok(runs.into_iter().find(|run| run.run_id.as_deref() == Some(run_id)));
+        }
+        if let Some(latest) = runs.into_iter().last() {
+            return Ok(Some(latest));
+        }
+
+        let [plain, compressed] = self.legacy_log_paths(job_id);
+        Ok([(plain, false), (compressed, true)]
+            .into_iter()
+            .find(|(path, _)| path.exists())
+            .map(|(path, compressed)| RunLog { run_id: None, path, started: None, finished: true, compressed }))
+    }
+
+    /// 一次执行开始时创建其日志，执行结束后调用 [`LogStore::finish_run_log`]
+    pub fn create_run_log(&self, job_id: &str, run_id: &str, started: DateTime<Utc>) -> Result<(PathBuf, File), Error> {
+        let write_error = |e: std::io::Error| Error::CrontabError(format!("写入日志失败: {}", e));
+        let dir = self.job_log_dir(job_id);
+        std::fs::create_dir_all(&dir).map_err(write_error)?;
+
+        let path = dir.join(format!("{}-{}{}", started.format(START_TIME_FORMAT), run_id, RUNNING_SUFFIX));
+        let file = File::create(&path).map_err(write_error)?;
+        Ok((path, file))
+    }
+
+    /// 一次执行结束：去掉日志的 `.part` 后缀，按设置压缩，并删除该任务超出保留数量的旧日志
+    pub fn finish_run_log(&self, path: &Path, settings: &LogSettings) -> Result<(), Error> {
+        let write_error = |e: std::io::Error| Error::CrontabError(format!("写入日志失败: {}", e));
+        let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        let Some(stem) = name.strip_suffix(RUNNING_SUFFIX) else {
+            return Ok(());
+        };
+        let finished = path.with_file_name(format!("{}{}", stem, FINISHED_SUFFIX));
+        std::fs::rename(path, &finished).map_err(write_error)?;
+
+        compress_if_needed(&finished, settings)?;
+        if let Some(job_id) = path.parent().and_then(Path::file_name).and_then(|name| name.to_str()) {
+            self.prune_runs(job_id).map_err(write_error)?;
+        }
+        Ok(())
+    }
+
+    /// 任务仍在执行中（日志以 `.part` 结尾）的各次执行的开始时间，按时间排列。
+    /// runner 异常退出时日志会一直保持执行中，这样的执行同样视为已经开始
+    pub fn running_since(&self, job_id: &str) -> Result<Vec<DateTime<Utc>>, Error> {
+        let runs = self.list_runs(job_id).map_err(read_error)?;
+        Ok(runs.into_iter().filter(|run| !run.finished).filter_map(|run| run.started).collect())
+    }
+
+    /// 删除超出保留数量的已结束日志与早期版本的共用日志，执行中的日志不计数也不删除
+    fn prune_runs(&self, job_id: &str) -> std::io::Result<()> {
+        let finished: Vec<_> = self.list_runs(job_id)?.into_iter().filter(|run| run.finished).collect();
+        let excess = finished.len().saturating_sub(KEEP_RUNS);
+        let stale = finished.iter().take(excess).map(|run| &run.path);
+        for path in stale.chain(self.legacy_log_paths(job_id).iter()) {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// 打开日志并定位到 `offset`，没有日志时返回 `None`。
+    /// 压缩的日志需要从头解压到 `offset`，`offset` 超过大小时定位到末尾。
+    /// 查找与打开之间日志可能刚好结束执行或被压缩而改名，此时按同一 `run_id` 重新查找
+    fn open_at(&self, job_id: &str, run_id: Option<&str>, offset: u64) -> Result<Option<OpenedLog>, Error> {
+        let mut attempts = 0;
+        loop {
+            let Some(run) = self.find_run(job_id, run_id)? else {
+                return Ok(None);
+            };
+            let opened = if run.compressed {
+                open_compressed(&run.path).map(|(decoder, size)| (Box::new(decoder) as Box<dyn Read>, size))
+            } else {
+                File::open(&run.path).and_then(|mut file| {
+                    let size = file.metadata()?.len();
+                    file.seek(SeekFrom::Start(offset.min(size)))?;
+                    Ok((Box::new(file) as Box<dyn Read>, size))
+                })
+            };
+            let (mut reader, size) = match opened {
+                Ok(opened) => opened,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && attempts < 3 => {
+                    attempts += 1;
+                    continue;
+                }
+                Err(e) => return Err(read_error(e)),
+            };
+
+            if run.compressed {
+                // 已读到末尾时不再解压，已结束的日志反复轮询也不会重复解压整个文件
+                if offset >= size {
+                    return Ok(Some(OpenedLog { run, reader: Box::new(std::io::empty()), size }));
+                }
+                std::io::copy(&mut (&mut reader).take(offset), &mut std::io::sink()).map_err(read_error)?;
+            }
+            return Ok(Some(OpenedLog { run, reader, size }));
+        }
+    }
+
+    /// 从头读取最近一次执行的完整日志（压缩的日志自动解压），没有日志时返回 `None`
+    pub fn open(&self, job_id: &str) -> Result<Option<Box<dyn Read>>, Error> {
+        Ok(self.open_at(job_id, None, 0)?.map(|log| log.reader))
+    }
+
+    /// 读取 `run_id` 指定的执行日志中 `offset` 之后的内容，最多 `max_bytes` 字节。
+    /// 未指定 `run_id` 时读取最近一次执行的日志
+    pub fn read_from(&self, job_id: &str, run_id: Option<&str>, offset: u64, max_bytes: u64) -> Result<LogChunk, Error> {
+        let (opened, reset) = match self.open_at(job_id, run_id, offset)? {
+            Some(log) => (Some(log), false),
+            None if run_id.is_some() => (self.open_at(job_id, None, 0)?, true),
+            None => (None, false),
+        };
+        let Some(OpenedLog { run, reader, size }) = opened else {
+            return Ok(LogChunk::empty(job_id));
+        };
+
+        let offset = if reset { 0 } else { offset.min(size) };
+        let bytes = read_bytes(reader, max_bytes.min(MAX_CHUNK_BYTES))?;
+        Ok(chunk(job_id, run, size, offset, &bytes, reset))
+    }
+
+    /// 读取 `run_id` 指定的执行日志末尾最多 `max_bytes` 字节，跳过开头不完整的一行。
+    /// 未指定 `run_id` 时读取最近一次执行的日志；指定的执行日志已不存在时与 [`LogStore::read_from`] 一样改读最近一次执行
+    pub fn read_tail(&self, job_id: &str, run_id: Option<&str>, max_bytes: u64) -> Result<LogChunk, Error> {
+        let (found, reset) = match self.find_run(job_id, run_id)? {
+            Some(run) => (Some(run), false),
+            None if run_id.is_some() => (self.find_run(job_id, None)?, true),
+            None => (None, false),
+        };
+        let Some(found) = found else {
+            return Ok(LogChunk::empty(job_id));
+        };
+        let run_id = found.run_id.as_deref();
+        let Some(OpenedLog { size, .. }) = self.open_at(job_id, run_id, 0)? else {
+            return Ok(LogChunk::empty(job_id));
+        };
+        let max_bytes = max_bytes.min(MAX_CHUNK_BYTES);
+        let mut offset = size.saturating_sub(max_bytes);
+
+        let Some(OpenedLog { run, reader, size }) = self.open_at(job_id, run_id, offset)? else {
+            return Ok(LogChunk::empty(job_id));
+        };
+        let mut bytes = read_bytes(reader, max_bytes)?;
+        if offset > 0 {
+            if let Some(newline) = bytes.iter().position(|byte| *byte == b'\n') {
+                bytes.drain(..=newline);
+                offset += newline as u64 + 1;
+            }
+        }
+
+        Ok(chunk(job_id, run, size, offset, &bytes, reset))
+    }
+
+    /// 按范围读取最近一次执行的日志
+    pub fn read_range(&self, job_id: &str, range: &LogRange) -> Result<LogChunk, Error> {
+        match range {
+            LogRange::Head(bytes) => self.read_from(job_id, None, 0, *bytes),
+            LogRange::Tail(bytes) => self.read_tail(job_id, None, *bytes),
+            LogRange::Range { offset, length } => self.read_from(job_id, None, *offset, *length),
+        }
+    }
+
+    /// 最近一次执行的日志的大小与压缩状态
+    pub fn log_info(&self, job_id: &str) -> Result<LogInfo, Error> {
+        let Some(run) = self.find_run(job_id, None)? else {
+            return Ok(LogInfo {
+                job_id: job_id.to_string(),
+                run_id: None,
+                exists: false,
+                size: 0,
+                stored_size: 0,
+                compressed: false,
+                finished: false,
+                modified: None,
+            });
+        };
+
+        let metadata = std::fs::metadata(&run.path).map_err(read_error)?;
+        let size = if run.compressed {
+            open_compressed(&run.path).map_err(read_error)?.1
+        } else {
+            metadata.len()
+        };
+
+        Ok(LogInfo {
+            job_id: job_id.to_string(),
+            run_id: run.run_id,
+            exists: true,
+            size,
+            stored_size: metadata.len(),
+            compressed: run.compressed,
+            finished: run.finished,
+            modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use crate::TempDir;
+
+    fn temp_store() -> (TempDir, LogStore) {
+        let dir = TempDir::new();
+        let logs = LogStore::new(dir.path().to_path_buf());
+        (dir, logs)
+    }
+
+    fn start(logs: &LogStore, run_id: &str, seconds: i64, output: &str) -> PathBuf {
+        let started = DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z").unwrap().with_timezone(&Utc)
+            + chrono::Duration::seconds(seconds);
+        let (path, mut file) = logs.create_run_log("job-1", run_id, started).unwrap();
+        file.write_all(output.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn overlapping_runs_keep_separate_logs() {
+        let (_dir, logs) = temp_store();
+        let first = start(&logs, "run-a", 0, "first\n");
+        start(&logs, "run-b", 1, "second\n");
+
+        let chunk = logs.read_from("job-1", Some("run-a"), 0, MAX_CHUNK_BYTES).unwrap();
+        assert_eq!(chunk.data, "first\n");
+        assert!(!chunk.finished && !chunk.reset);
+
+        let latest = logs.read_tail("job-1", None, DEFAULT_TAIL_BYTES).unwrap();
+        assert_eq!(latest.run_id.as_deref(), Some("run-b"));
+        assert_eq!(latest.data, "second\n");
+        let tail = logs.read_tail("job-1", Some("run-a"), DEFAULT_TAIL_BYTES).unwrap();
+        assert_eq!(tail.run_id.as_deref(), Some("run-a"));
+        assert_eq!(tail.data, "first\n");
+
+        logs.finish_run_log(&first, &LogSettings::default()).unwrap();
+        let chunk = logs.read_from("job-1", Some("run-a"), chunk.next_offset, MAX_CHUNK_BYTES).unwrap();
+        assert!(chunk.finished && chunk.data.is_empty());
+        assert_eq!(chunk.next_offset, chunk.size);
+
+        // 正在查看的执行日志被删除后改为从头读取最近一次执行
+        std::fs::remove_file(first.with_extension("")).unwrap();
+        let chunk = logs.read_from("job-1", Some("run-a"), 6, MAX_CHUNK_BYTES).unwrap();
+        assert!(chunk.reset);
+        assert_eq!(chunk.run_id.as_deref(), Some("run-b"));
+        assert_eq!(chunk.data, "second\n");
+        let tail = logs.read_tail("job-1", Some("run-a"), DEFAULT_TAIL_BYTES).unwrap();
+        assert!(tail.reset);
+        assert_eq!(tail.run_id.as_deref(), Some("run-b"));
+    }
+
+    #[test]
+    fn read_tail_without_a_log_is_empty() {
+        let (_dir, logs) = temp_store();
+        let chunk = logs.read_tail("job-1", None, DEFAULT_TAIL_BYTES).unwrap();
+        assert!(chunk.data.is_empty() && chunk.run_id.is_none());
+        assert!(!logs.log_info("job-1").unwrap().exists);
+    }
+
+    #[test]
+    fn read_tail_skips_the_partial_first_line() {
+        let (_dir, logs) = temp_store();
+        start(&logs, "run-a", 0, "line 1\nline 2\nline 3\n");
+
+        let chunk = logs.read_tail("job-1", None, 10).unwrap();
+        assert_eq!(chunk.data, "line 3\n");
+        assert_eq!(chunk.offset, 14);
+        assert_eq!((chunk.next_offset, chunk.size), (21, 21));
+        assert!(chunk.truncated && !chunk.finished);
+
+        let whole = logs.read_tail("job-1", None, DEFAULT_TAIL_BYTES).unwrap();
+        assert_eq!(whole.data, "line 1\nline 2\nline 3\n");
+        assert!(!whole.truncated);
+    }
+
+    #[test]
+    fn read_tail_leaves_an_incomplete_character_for_the_next_read() {
+        let (_dir, logs) = temp_store();
+        let path = start(&logs, "run-a", 0, "日志\n第二");
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&"行".as_bytes()[..1]).unwrap();
+
+        let chunk = logs.read_tail("job-1", None, DEFAULT_TAIL_BYTES).unwrap();
+        assert_eq!(chunk.data, "日志\n第二");
+        file.write_all(&"行".as_bytes()[1..]).unwrap();
+        let next = logs.read_from("job-1", chunk.run_id.as_deref(), chunk.next_offset, MAX_CHUNK_BYTES).unwrap();
+        assert_eq!(next.data, "行");
+    }
+
+    #[test]
+    fn compressed_logs_read_like_plain_ones() {
+        let (_dir, logs) = temp_store();
+        let output = "line\n".repeat(1000);
+        let finished = start(&logs, "run-a", 0, &output);
+        let running = start(&logs, "run-b", 1, "still going\n");
+        let settings = LogSettings { compress: true, compress_min_bytes: 0 };
+        logs.finish_run_log(&finished, &settings).unwrap();
+
+        // 压缩只删除这一次执行的日志，正在执行的日志不受影响
+        assert!(running.exists());
+        let run = logs.find_run("job-1", Some("run-a")).unwrap().unwrap();
+        assert!(run.compressed && run.finished);
+        assert_eq!(open_compressed(&run.path).unwrap().1, output.len() as u64);
+
+        let chunk = logs.read_from("job-1", Some("run-a"), 0, MAX_CHUNK_BYTES).unwrap();
+        assert_eq!(chunk.data, output);
+        let tail = logs.read_from("job-1", Some("run-a"), output.len() as u64 - 5, MAX_CHUNK_BYTES).unwrap();
+        assert_eq!(tail.data, "line\n");
+        let end = logs.read_from("job-1", Some("run-a"), output.len() as u64, MAX_CHUNK_BYTES).unwrap();
+        assert!(end.finished && end.data.is_empty());
+    }
+
+    #[test]
+    fn original_size_above_4_gb_is_kept_in_the_header() {
+        let size = 5 * 1024 * 1024 * 1024;
+        let mut extra = vec![b'X', b'X', 2, 0, 0, 0];
+        extra.extend(size_extra(size));
+        assert_eq!(stored_size(&extra), Some(size));
+        assert_eq!(stored_size(&extra[..6]), None);
+    }
+
+    #[test]
+    fn finishing_a_run_prunes_old_logs_but_not_running_ones() {
+        let (_dir, logs) = temp_store();
+        let running = start(&logs, "running", 0, "still going\n");
+        for i in 1..=KEEP_RUNS as i64 + 2 {
+            logs.finish_run_log(&start(&logs, &format!("run-{}", i), i, "done\n"), &LogSettings::default()).unwrap();
+        }
+
+        let runs = logs.list_runs("job-1").unwrap();
+        assert_eq!(runs.len(), KEEP_RUNS + 1);
+        assert_eq!(runs[0].path, running);
+        assert!(runs[1..].iter().all(|run| run.finished));
+        assert_eq!(runs.last().unwrap().run_id.as_deref(), Some(format!("run-{}", KEEP_RUNS + 2).as_str()));
+    }
+}
@@ -3,50 +3,44 @@
     windows_subsystem = "windows"
 )]
 
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use std::process::Command;
 use uuid::Uuid;
 use std::sync::Mutex;
+use std::time::Duration;
+use tauri::Manager;
 use lazy_static::lazy_static;
-use thiserror::Error;
-use chrono::{DateTime, Utc, Local};
-use chrono::{Datelike, Timelike};
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use cron::Schedule;
-use std::str::FromStr;
-mod db;
+mod api;
+mod desktop_notify;
 mod permissions;
-mod python;
 
-use db::Database;
-use python::PythonEnv;
+use crontab_ui::db::{Database, DbCheckReport, GlobalStats, HistoryPage, HistoryQuery, JobAggregate, JobStats, LogSearchPage, LogSearchQuery};
+use crontab_ui::missed::{self, MissedRunReport};
+use crontab_ui::python::PythonEnv;
+use crontab_ui::deps::{self, DependencyGraph};
+use crontab_ui::drift::{self, DriftReport, DriftResolution};
+use crontab_ui::crontab::{CrontabExportMode, CrontabVariable};
+use crontab_ui::transfer::{self, ConflictStrategy, ExportFormat, ImportReport};
+use crontab_ui::email::{self, SmtpSettings, SmtpSettingsView};
+use crontab_ui::notify::WebhookPayload;
+use crontab_ui::capture::{self, CaptureSettings, OutputStream};
+use crontab_ui::logs::{self, LogChunk, LogInfo, LogRange, LogSettings, LogStore};
+use crontab_ui::{crontab, notify, runner, schedule, CronJob, Error, JobDependency, JobHistory, RunTrigger, SuccessRule, WebhookRule};
+use api::{ApiServer, ApiServerSettings};
+use desktop_notify::DesktopNotificationSettings;
 use std::sync::Arc;
-use tauri::api::path::app_data_dir;
+use std::sync::atomic::{AtomicBool, Ordering};
 use permissions::{check_crontab_permissions, check_data_dir_permissions};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct CronJob {
-    id: String,
-    name: Option<String>,
-    schedule: String,
-    command: String,
-    is_active: bool,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct JobHistory {
-    id: String,
-    job_id: String,
-    execution_time: DateTime<Utc>,
-    status: JobStatus,
-    output: String,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-enum JobStatus {
-    Success,
-    Failed,
+#[derive(Debug, Serialize, Clone)]
+struct JobStatsReport {
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    jobs: Vec<JobStats>,
+    global: GlobalStats,
 }
 
 lazy_static! {
@@ -56,90 +50,122 @@ lazy_static! {
         let home = std::env::var("HOME").expect("Failed to get HOME directory");
         Arc::new(PythonEnv::new(&home).expect("Failed to initialize Python environment"))
     };
+    // 应用最近一次看到的 crontab 内容摘要，后台监听据此判断 crontab 是否在应用之外被修改
+    static ref CRONTAB_HASH: Mutex<Option<u64>> = Mutex::new(None);
+    // 正在实时查看的任务日志，置为 true 时对应的轮询线程退出
+    static ref LOG_FOLLOWERS: Mutex<HashMap<String, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
     static ref DB: Arc<Database> = {
-        let app_data_dir = app_data_dir(&tauri::Config::default())
+        let app_data_dir = crontab_ui::data_dir()
             .expect("Failed to get app data directory");
         Arc::new(Database::new(app_data_dir).expect("Failed to initialize database"))
     };
+    // 运行中的本地 HTTP 接口服务，未开启时为空
+    static ref API_SERVER: Mutex<Option<ApiServer>> = Mutex::new(None);
 }
 
-#[derive(Debug, thiserror::Error)]
-enum Error {
-    #[error("Invalid cron expression: {0}")]
-    InvalidCronExpression(String),
-    
-    #[error("Invalid command: {0}")]
-    InvalidCommand(String),
-    
-    #[error("Failed to update crontab: {0}")]
-    CrontabError(String),
-    
-    #[error("Job not found")]
-    JobNotFound,
-
-    #[error("Database error: {0}")]
-    DatabaseError(String),
-
-    #[error("Permission error: {0}")]
-    PermissionError(String),
-
-    #[error("Python error: {0}")]
-    PythonError(String),
+#[derive(Debug, Serialize, Clone)]
+struct TagSummary {
+    tag: String,
+    job_count: u64,
 }
 
-impl serde::Serialize for Error {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_str(self.to_string().as_ref())
+#[tauri::command]
+async fn get_cron_jobs(tag: Option<String>) -> Result<Vec<CronJob>, Error> {
+    match tag {
+        Some(tag) => DB.get_jobs_by_tag(&tag),
+        None => DB.get_all_jobs(),
     }
+    .map_err(|e| Error::DatabaseError(e.to_string()))
 }
 
 #[tauri::command]
-async fn get_cron_jobs() -> Result<Vec<CronJob>, Error> {
-    DB.get_all_jobs()
+async fn get_tags() -> Result<Vec<TagSummary>, Error> {
+    let tags = DB.list_tags()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    Ok(tags.into_iter().map(|(tag, job_count)| TagSummary { tag, job_count }).collect())
+}
+
+#[tauri::command]
+async fn set_job_tags(id: String, tags: Vec<String>) -> Result<(), Error> {
+    let mut job = DB.get_job(&id)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .ok_or(Error::JobNotFound)?;
+    job.tags = tags;
+    DB.update_job(&job)
         .map_err(|e| Error::DatabaseError(e.to_string()))
 }
 
-// 验证 cron 表达式
-fn validate_cron_expression(expression: &str) -> Result<(), Error> {
-    if expression.trim().is_empty() {
-        return Err(Error::InvalidCronExpression("Cron expression cannot be empty".into()));
+// 批量启用或停用某个标签下的所有任务，crontab 只写入一次。
+// 启用时先逐个检查，任一任务无效（例如从 crontab 文件导入后默认停用的任务）则整批不启用
+#[tauri::command]
+async fn set_tag_active(tag: String, is_active: bool) -> Result<usize, Error> {
+    let jobs = DB.get_jobs_by_tag(&tag)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    if is_active {
+        for job in &jobs {
+            job.validate()?;
+        }
     }
-    
-    // 这里可以添加更复杂的 cron 表达式验证
-    Ok(())
+
+    let ids: Vec<String> = jobs.iter().map(|job| job.id.clone()).collect();
+    DB.set_jobs_active(&ids, is_active)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    sync_crontab(&ids.iter().map(String::as_str).collect::<Vec<_>>())?;
+
+    Ok(ids.len())
 }
 
-// 验证命令
-fn validate_command(command: &str) -> Result<(), Error> {
-    // 检查命令是否为空
-    if command.trim().is_empty() {
-        return Err(Error::InvalidCommand("Command cannot be empty".to_string()));
-    }
-    
-    // 移除危险命令检测或仅保留极其危险的命令检测
-    // 例如，只检测删除根目录的命令
-    if command.contains("rm -rf /") {
-        return Err(Error::InvalidCommand("Extremely dangerous command detected".to_string()));
-    }
-    
-    Ok(())
+// 批量删除某个标签下的所有任务，crontab 只写入一次
+#[tauri::command]
+async fn delete_tag_jobs(tag: String) -> Result<usize, Error> {
+    let jobs = DB.get_jobs_by_tag(&tag)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    let ids: Vec<String> = jobs.iter().map(|job| job.id.clone()).collect();
+
+    let deleted = DB.delete_jobs(&ids)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    sync_crontab(&ids.iter().map(String::as_str).collect::<Vec<_>>())?;
+
+    Ok(deleted)
 }
 
 #[tauri::command]
-async fn add_cron_job(name: Option<String>, schedule: String, command: String) -> Result<(), Error> {
-    validate_cron_expression(&schedule)?;
-    validate_command(&command)?;
-    
+#[allow(clippy::too_many_arguments)]
+async fn add_cron_job(
+    name: Option<String>,
+    schedule: String,
+    command: String,
+    catch_up: Option<bool>,
+    max_lateness_minutes: Option<u32>,
+    depends_on: Option<Vec<JobDependency>>,
+    tags: Option<Vec<String>>,
+    webhooks: Option<Vec<WebhookRule>>,
+    email_recipients: Option<Vec<String>>,
+    mailto: Option<String>,
+    success_rules: Option<Vec<SuccessRule>>,
+) -> Result<(), Error> {
     let new_job = CronJob {
         id: Uuid::new_v4().to_string(),
         name,
         schedule,
         command,
         is_active: false,  // 默认为关闭状态
+        catch_up: catch_up.unwrap_or(false),
+        max_lateness_minutes,
+        depends_on: depends_on.unwrap_or_default(),
+        tags: tags.unwrap_or_default(),
+        webhooks: webhooks.unwrap_or_default(),
+        email_recipients: email_recipients.unwrap_or_default(),
+        mailto: mailto.filter(|mailto| !mailto.trim().is_empty()),
+        success_rules: success_rules.unwrap_or_default(),
+        ..CronJob::default()
     };
+
+    new_job.validate()?;
+
+    let jobs = DB.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    deps::validate_dependencies(&jobs, &new_job)?;
     
     DB.add_job(&new_job)
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
@@ -152,16 +178,95 @@ async fn toggle_cron_job(id: String, is_active: bool) -> Result<(), Error> {
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
     
     if let Some(mut job) = jobs.into_iter().find(|j| j.id == id) {
+        // 启用前检查，避免把无法执行的任务写入 crontab
+        if is_active {
+            job.validate()?;
+        }
         job.is_active = is_active;
         DB.update_job(&job)
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
-        update_crontab(&job)?;
+        sync_crontab(&[&job.id])?;
         Ok(())
     } else {
         Err(Error::JobNotFound)
     }
 }
 
+// 立即执行任务，并按依赖触发下游任务，返回的第一条为任务本身的执行记录
+#[tauri::command]
+async fn run_cron_job(id: String) -> Result<Vec<JobHistory>, Error> {
+    let job = DB.get_job(&id)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .ok_or(Error::JobNotFound)?;
+    runner::run_chain(&DB, &job, RunTrigger::Manual)
+}
+
+// 向指定地址发送一条测试通知，使用任务最近一次的执行结果
+#[tauri::command]
+async fn test_webhook(job_id: String, url: String) -> Result<(), Error> {
+    let job = DB.get_job(&job_id)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .ok_or(Error::JobNotFound)?;
+    let latest = DB.get_job_history(&job_id)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .into_iter()
+        .next();
+    notify::send_test(&url, &job, latest.as_ref())
+}
+
+fn load_smtp_settings() -> Result<Option<SmtpSettings>, Error> {
+    DB.get_setting(email::SETTINGS_KEY)
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
+
+// 读取 SMTP 设置，不返回已保存的密码
+#[tauri::command]
+async fn get_smtp_settings() -> Result<SmtpSettingsView, Error> {
+    Ok(load_smtp_settings()?.unwrap_or_default().into())
+}
+
+// 保存 SMTP 设置，未提交密码时保留已保存的密码
+#[tauri::command]
+async fn update_smtp_settings(settings: SmtpSettings) -> Result<(), Error> {
+    let settings = settings.with_stored_password(load_smtp_settings()?.as_ref());
+    DB.set_setting(email::SETTINGS_KEY, &settings)
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
+
+// 用传入的设置发送一封测试邮件，设置无需先保存；未提交密码时使用已保存的密码
+#[tauri::command]
+async fn test_smtp(settings: SmtpSettings, to: String) -> Result<(), Error> {
+    let settings = settings.with_stored_password(load_smtp_settings()?.as_ref());
+    let job = CronJob {
+        id: "test".to_string(),
+        name: Some("测试通知".to_string()),
+        ..CronJob::default()
+    };
+    let payload = WebhookPayload::new(&job, &notify::sample_history(&job.id), None);
+    email::send_email(&settings, &[to], &payload)
+}
+
+#[tauri::command]
+async fn get_desktop_notification_settings() -> Result<DesktopNotificationSettings, Error> {
+    desktop_notify::load_settings(&DB)
+}
+
+#[tauri::command]
+async fn update_desktop_notification_settings(settings: DesktopNotificationSettings) -> Result<(), Error> {
+    desktop_notify::save_settings(&DB, &settings)
+}
+
+// 单独设置某个任务是否发送系统通知
+#[tauri::command]
+async fn set_job_notifications_muted(job_id: String, muted: bool) -> Result<(), Error> {
+    let updated = DB.set_job_notifications_muted(&job_id, muted)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    if !updated {
+        return Err(Error::JobNotFound);
+    }
+    Ok(())
+}
+
 fn restart_crontab() -> Result<(), Error> {
     // 停止 cron 服务
     Command::new("sudo")
@@ -231,83 +336,78 @@ EOL" password pwd with administrator privileges
     Ok(())
 }
 
-// 更新 crontab 并重启服务
-fn update_and_restart_crontab(content: &str) -> Result<(), Error> {
-    // 更新 crontab
-    Command::new("sh")
-        .arg("-c")
-        .arg(format!("echo '{}' | crontab -", content))
-        .output()
-        .map_err(|e| Error::CrontabError(e.to_string()))?;
-
-    // 重启 crontab 服务前检查权限
+// 重启 cron 服务，重启前检查权限
+fn restart_crontab_service() -> Result<(), Error> {
     if !check_sudo_access()? {
         request_sudo_password()?;
     }
 
-    // 重启 crontab 服务
-    restart_crontab()?;
-
-    Ok(())
+    restart_crontab()
 }
 
-fn update_crontab(job: &CronJob) -> Result<(), Error> {
-    let home = std::env::var("HOME").unwrap_or_default();
-
-    // 获取当前的 crontab 内容
-    let current_crontab = Command::new("crontab")
-        .arg("-l")
-        .output()
-        .map_err(|e| Error::CrontabError(e.to_string()))?;
+// 按数据库中的任务重新生成 crontab 的受管理区段，内容有变化时才写入并重启一次服务。
+// 与数据库不一致的条目保留原样等待用户处理，`job_ids` 是本次在应用中修改的任务，以数据库为准
+fn sync_crontab(job_ids: &[&str]) -> Result<(), Error> {
+    // 写入期间持有锁，后台监听不会在写入与记录摘要之间读到应用自己的修改
+    let mut known_hash = CRONTAB_HASH.lock().unwrap();
+    let jobs = DB.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-    let current_content = String::from_utf8_lossy(&current_crontab.stdout);
-    
-    // 移除旧的任务配置（如果存在）
-    let mut new_content: Vec<String> = Vec::new();
-    let mut skip_lines = false;
-    
-    for line in current_content.lines() {
-        if line.contains(&format!("# JOB_ID:{}", job.id)) {
-            skip_lines = true;
-            continue;
-        }
-        
-        if skip_lines {
-            if line.trim().is_empty() {
-                skip_lines = false;
-            }
-            continue;
-        }
-        
-        new_content.push(line.to_string());
+    if crontab::reconcile(&jobs, job_ids)? {
+        restart_crontab_service()?;
     }
 
-    // 如果任务是激活状态，添加新的配置
-    if job.is_active {
-        // 创建 crontab 输出目录结构
-        let base_dir = PathBuf::from(&home).join(".crontab");
-        std::fs::create_dir_all(&base_dir)
-            .map_err(|e| Error::CrontabError(format!("创建输出目录失败: {}", e)))?;
+    // 记录写入后的内容，避免后台监听把应用自己的修改当作外部修改
+    let content = crontab::read_crontab()?;
+    *known_hash = Some(crontab::content_hash(&content));
 
-        let log_file = base_dir.join(format!("{}.log", job.id));
+    Ok(())
+}
+
+// crontab 在应用之外被修改时发送给前端的事件
+const CRONTAB_CHANGED_EVENT: &str = "crontab-changed";
 
-        let wrapped_command = format!("{} > '{}' 2>&1", job.command, log_file.display());
+// 后台监听 crontab 的轮询间隔
+const CRONTAB_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
-        let wrapped_command = format!(
-            "\n# JOB_ID:{}\n# NAME:{}\n{} {}",
-            job.id,
-            job.name.as_deref().unwrap_or(""),
-            job.schedule,
-            wrapped_command,
-        );
+#[derive(Debug, Serialize, Clone)]
+struct CrontabChanged {
+    drift: DriftReport,
+}
 
-        new_content.push(wrapped_command);
+// 检查 crontab 是否在应用之外被修改（例如 `crontab -e`），有修改时返回不一致报告。
+// 这里只读取不写入：写入 crontab 可能需要授权，只在用户操作时进行，由用户在漂移报告中选择以哪一侧为准
+fn check_external_crontab_change() -> Result<Option<DriftReport>, Error> {
+    let mut known_hash = CRONTAB_HASH.lock().unwrap();
+    let content = crontab::read_crontab()?;
+    let hash = crontab::content_hash(&content);
+    if *known_hash == Some(hash) {
+        return Ok(None);
     }
+    // 同一次外部修改只通知一次
+    *known_hash = Some(hash);
+    drop(known_hash);
 
-    // 更新 crontab 并重启服务
-    update_and_restart_crontab(&new_content.join("\n"))?;
+    let jobs = DB.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    Ok(Some(drift::detect_drift(&content, &jobs)))
+}
 
-    Ok(())
+// 后台轮询 crontab，发现外部修改时通知前端刷新
+fn spawn_crontab_watcher(app: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(CRONTAB_POLL_INTERVAL);
+
+        match check_external_crontab_change() {
+            Ok(Some(drift)) => {
+                if let Err(e) = app.emit_all(CRONTAB_CHANGED_EVENT, CrontabChanged { drift }) {
+                    eprintln!("Failed to emit crontab change: {}", e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to check crontab changes: {}", e),
+        }
+    });
 }
 
 #[tauri::command]
@@ -315,42 +415,11 @@ async fn delete_cron_job(id: String) -> Result<(), Error> {
     let jobs = DB.get_all_jobs()
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
     
-    if let Some(job) = jobs.into_iter().find(|j| j.id == id) {
-        // 只有当任务处于激活状态时才更新系统 crontab
-        if job.is_active {
-            // 获取当前 crontab 内容并移除指定任务
-            let current_crontab = Command::new("crontab")
-                .arg("-l")
-                .output()
-                .map_err(|e| Error::CrontabError(e.to_string()))?;
-
-            let current_content = String::from_utf8_lossy(&current_crontab.stdout);
-            let mut new_content: Vec<String> = Vec::new();
-            let mut skip_lines = false;
-            
-            for line in current_content.lines() {
-                if line.contains(&format!("# JOB_ID:{}", job.id)) {
-                    skip_lines = true;
-                    continue;
-                }
-                
-                if skip_lines {
-                    if line.trim().is_empty() {
-                        skip_lines = false;
-                    }
-                    continue;
-                }
-                
-                new_content.push(line.to_string());
-            }
-
-            // 更新 crontab 并重启服务
-            update_and_restart_crontab(&new_content.join("\n"))?;
-        }
-        
-        // 从数据库中删除任务
+    if jobs.iter().any(|j| j.id == id) {
+        // 从数据库中删除任务，并移除 crontab 中该任务的条目
         DB.delete_job(&id)
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        sync_crontab(&[&id])?;
         Ok(())
     } else {
         Err(Error::JobNotFound)
@@ -358,14 +427,25 @@ async fn delete_cron_job(id: String) -> Result<(), Error> {
 }
 
 #[tauri::command]
-async fn update_cron_job(id: String, name: Option<String>, schedule: String, command: String) -> Result<(), Error> {
-    validate_cron_expression(&schedule)?;
-    validate_command(&command)?;
-
+#[allow(clippy::too_many_arguments)]
+async fn update_cron_job(
+    id: String,
+    name: Option<String>,
+    schedule: String,
+    command: String,
+    catch_up: Option<bool>,
+    max_lateness_minutes: Option<u32>,
+    depends_on: Option<Vec<JobDependency>>,
+    tags: Option<Vec<String>>,
+    webhooks: Option<Vec<WebhookRule>>,
+    email_recipients: Option<Vec<String>>,
+    mailto: Option<String>,
+    success_rules: Option<Vec<SuccessRule>>,
+) -> Result<(), Error> {
     let jobs = DB.get_all_jobs()
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
     
-    if let Some(mut job) = jobs.into_iter().find(|j| j.id == id) {
+    if let Some(mut job) = jobs.iter().find(|j| j.id == id).cloned() {
         // 保存旧的激活状态
         let was_active = job.is_active;
         
@@ -373,6 +453,34 @@ async fn update_cron_job(id: String, name: Option<String>, schedule: String, com
         job.name = name;
         job.schedule = schedule;
         job.command = command;
+        // 未传入补执行策略时保持原设置
+        if let Some(catch_up) = catch_up {
+            job.catch_up = catch_up;
+            job.max_lateness_minutes = max_lateness_minutes;
+        }
+        // 未传入依赖时保持原设置
+        if let Some(depends_on) = depends_on {
+            job.depends_on = depends_on;
+        }
+        if let Some(tags) = tags {
+            job.tags = tags;
+        }
+        if let Some(webhooks) = webhooks {
+            job.webhooks = webhooks;
+        }
+        if let Some(email_recipients) = email_recipients {
+            job.email_recipients = email_recipients;
+        }
+        // 传入空字符串时清除 MAILTO
+        if let Some(mailto) = mailto {
+            job.mailto = Some(mailto).filter(|mailto| !mailto.trim().is_empty());
+        }
+        if let Some(success_rules) = success_rules {
+            job.success_rules = success_rules;
+        }
+
+        job.validate()?;
+        deps::validate_dependencies(&jobs, &job)?;
         
         // 更新数据库
         DB.update_job(&job)
@@ -380,7 +488,7 @@ async fn update_cron_job(id: String, name: Option<String>, schedule: String, com
         
         // 只有当任务处于激活状态时才更新系统 crontab
         if was_active {
-            update_crontab(&job)?;
+            sync_crontab(&[&job.id])?;
         }
         
         Ok(())
@@ -389,6 +497,106 @@ async fn update_cron_job(id: String, name: Option<String>, schedule: String, com
     }
 }
 
+// 比较数据库与 crontab，返回两侧不一致的任务
+#[tauri::command]
+async fn get_crontab_drift() -> Result<DriftReport, Error> {
+    let jobs = DB.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    let content = crontab::read_crontab()?;
+    Ok(drift::detect_drift(&content, &jobs))
+}
+
+// 按用户的选择处理一个任务的不一致，返回处理后剩余的不一致
+#[tauri::command]
+async fn resolve_crontab_drift(job_id: String, resolution: DriftResolution) -> Result<DriftReport, Error> {
+    let report = get_crontab_drift().await?;
+    let item = report.find(&job_id).ok_or(Error::JobNotFound)?;
+
+    drift::resolve_drift(&DB, item, resolution)?;
+    sync_crontab(&[&job_id])?;
+
+    get_crontab_drift().await
+}
+
+// 读取 crontab 受管理区段之外的环境变量（如 `MAILTO`、`PATH`、`SHELL`）
+#[tauri::command]
+async fn get_crontab_variables() -> Result<Vec<CrontabVariable>, Error> {
+    crontab::parse_variables(&crontab::read_crontab()?)
+}
+
+// 按传入的列表更新 crontab 环境变量，未列出的变量会被删除
+#[tauri::command]
+async fn set_crontab_variables(variables: Vec<CrontabVariable>) -> Result<(), Error> {
+    let mut known_hash = CRONTAB_HASH.lock().unwrap();
+    if crontab::update_variables(&variables)? {
+        restart_crontab_service()?;
+    }
+
+    let content = crontab::read_crontab()?;
+    *known_hash = Some(crontab::content_hash(&content));
+    Ok(())
+}
+
+// 导出全部或选中的任务
+#[tauri::command]
+async fn export_jobs(ids: Option<Vec<String>>, format: ExportFormat) -> Result<String, Error> {
+    let mut jobs = DB.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    if let Some(ids) = ids {
+        jobs.retain(|job| ids.contains(&job.id));
+    }
+    transfer::export_jobs(&jobs, format)
+}
+
+// 导入任务，`dry_run` 时只返回导入计划
+#[tauri::command]
+async fn import_jobs(
+    content: String,
+    format: ExportFormat,
+    strategy: Option<ConflictStrategy>,
+    dry_run: Option<bool>,
+) -> Result<ImportReport, Error> {
+    let document = transfer::parse_document(&content, format)?;
+    let report = transfer::import_jobs(&DB, &document, strategy.unwrap_or_default(), dry_run.unwrap_or(false))?;
+
+    if !report.dry_run {
+        sync_crontab(&report.imported_ids())?;
+    }
+
+    Ok(report)
+}
+
+// 导出为标准 crontab 片段，未指定任务时导出所有启用中的任务
+#[tauri::command]
+async fn export_crontab(ids: Option<Vec<String>>, mode: Option<CrontabExportMode>) -> Result<String, Error> {
+    let mut jobs = DB.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    match ids {
+        Some(ids) => jobs.retain(|job| ids.contains(&job.id)),
+        None => jobs.retain(|job| job.is_active),
+    }
+    crontab::export_crontab(&jobs, mode.unwrap_or_default())
+}
+
+// 从磁盘上的 crontab 文件导入任务，导入的任务默认停用
+#[tauri::command]
+async fn import_crontab_file(
+    path: String,
+    strategy: Option<ConflictStrategy>,
+    dry_run: Option<bool>,
+) -> Result<ImportReport, Error> {
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| Error::InvalidImport(format!("读取 {} 失败: {}", path, e)))?;
+    let document = transfer::document_from_crontab(&content);
+    let report = transfer::import_jobs(&DB, &document, strategy.unwrap_or_default(), dry_run.unwrap_or(false))?;
+
+    if !report.dry_run {
+        sync_crontab(&report.imported_ids())?;
+    }
+
+    Ok(report)
+}
+
 #[tauri::command]
 async fn get_job_history(job_id: String) -> Result<Vec<JobHistory>, Error> {
     DB.get_job_history(&job_id)
@@ -396,130 +604,198 @@ async fn get_job_history(job_id: String) -> Result<Vec<JobHistory>, Error> {
 }
 
 #[tauri::command]
-async fn record_job_history(job_id: String, output: String, status: JobStatus) -> Result<(), Error> {
-    let history_entry = JobHistory {
-        id: Uuid::new_v4().to_string(),
-        job_id: job_id.clone(),
-        execution_time: Utc::now(),
-        status,
-        output,
-    };
+async fn query_job_history(query: HistoryQuery) -> Result<HistoryPage, Error> {
+    DB.query_history(&query)
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
 
-    DB.add_history(&history_entry)
+// 在所有任务的执行输出中全文搜索
+#[tauri::command]
+async fn search_logs(query: LogSearchQuery) -> Result<LogSearchPage, Error> {
+    DB.search_logs(&query)
         .map_err(|e| Error::DatabaseError(e.to_string()))
 }
 
-// 添加初始化函数
-fn initialize_app() -> Result<(), Error> {
-    // 确保数据库已初始化
-    lazy_static::initialize(&DB);
+// 一次执行保存的输出，`stream` 为空时返回合并的输出
+#[tauri::command]
+async fn get_history_output(history_id: String, stream: Option<OutputStream>) -> Result<Option<String>, Error> {
+    DB.get_history_output(&history_id, stream)
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
 
-    // 初始化 Python 虚拟环境
-    PYTHON_ENV.initialize()?;
+#[tauri::command]
+async fn get_capture_settings() -> Result<CaptureSettings, Error> {
+    let settings = DB.get_setting(capture::SETTINGS_KEY)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    Ok(settings.unwrap_or_default())
+}
 
-    // 从 crontab 加载现有任务
-    let output = Command::new("crontab")
-        .arg("-l")
-        .output()
-        .map_err(|e| Error::CrontabError(e.to_string()))?;
+#[tauri::command]
+async fn update_capture_settings(settings: CaptureSettings) -> Result<(), Error> {
+    DB.set_setting(capture::SETTINGS_KEY, &settings)
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
 
-    // 获取数据库中的所有任务
-    let mut db_jobs = DB.get_all_jobs()
+#[tauri::command]
+async fn get_job_aggregates(job_id: Option<String>) -> Result<Vec<JobAggregate>, Error> {
+    DB.get_job_aggregates(job_id.as_deref())
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
+
+#[tauri::command]
+async fn get_job_stats(window_days: Option<u32>) -> Result<JobStatsReport, Error> {
+    let window_end = Utc::now();
+    let window_start = window_end - chrono::Duration::days(window_days.unwrap_or(7) as i64);
+
+    let mut jobs = DB.get_job_stats(window_start, window_end)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    let mut global = DB.get_global_stats(window_start, window_end)
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-    // 先将所有任务标记为非活动状态
-    for job in &mut db_jobs {
-        job.is_active = false;
-        DB.update_job(&job)
+    // 期望执行次数无法在 SQL 中计算，根据计划表达式补充；只统计启用中的任务，
+    // 且与补执行一致，从任务创建或最近一次启用（较晚者）开始计算，停用期间不算错过
+    let active_jobs: HashMap<String, CronJob> = DB.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .into_iter()
+        .filter(|job| job.is_active)
+        .map(|job| (job.id.clone(), job))
+        .collect();
+
+    for stats in &mut jobs {
+        let Some(job) = active_jobs.get(&stats.job_id) else {
+            continue;
+        };
+        let active_since = DB.get_job_active_since(&job.id)
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        let from = active_since.map_or(window_start, |active_since| active_since.max(window_start));
+        if let Ok(Some(expected)) = schedule::count_fire_times(&job.schedule, from, window_end) {
+            let missed = expected.saturating_sub(stats.scheduled_runs);
+            stats.expected_runs = Some(expected);
+            stats.missed_runs = Some(missed);
+            global.expected_runs += expected;
+            global.missed_runs += missed;
+        }
     }
 
-    // 创建一个 HashSet 来跟踪系统 crontab 中的任务 ID
-    let mut crontab_job_ids = std::collections::HashSet::new();
+    Ok(JobStatsReport { window_start, window_end, jobs, global })
+}
 
-    if output.status.success() {
-        let crontab_content = String::from_utf8_lossy(&output.stdout);
-        let mut current_job_id: Option<String> = None;
-        let mut current_job_name: Option<String> = None;
-        let mut lines = crontab_content.lines().peekable();
-
-        while let Some(line) = lines.next() {
-            if line.trim().is_empty() {
-                current_job_id = None;
-                current_job_name = None;
-                continue;
-            }
+#[tauri::command]
+async fn check_database() -> Result<DbCheckReport, Error> {
+    DB.check()
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
 
-            // 检查是否是我们的任务标记
-            if line.starts_with("# JOB_ID:") {
-                current_job_id = Some(line.trim_start_matches("# JOB_ID:").trim().to_string());
-                continue;
-            }
+#[tauri::command]
+async fn repair_database() -> Result<DbCheckReport, Error> {
+    DB.repair()
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
 
-            if line.starts_with("# NAME:") {
-                current_job_name = Some(line.trim_start_matches("# NAME:").trim().to_string());
-                continue;
-            }
+#[tauri::command]
+async fn check_missed_runs(hours: Option<u32>, catch_up: Option<bool>) -> Result<MissedRunReport, Error> {
+    let checked_to = Utc::now();
+    let checked_from = checked_to - chrono::Duration::hours(hours.unwrap_or(24) as i64);
 
-            // 跳过其他注释行
-            if line.starts_with('#') {
-                continue;
-            }
+    let jobs = DB.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    let missed = missed::find_missed_runs(
+        &DB,
+        &LogStore::default(),
+        &jobs,
+        checked_from,
+        checked_to,
+        chrono::Duration::minutes(missed::DEFAULT_GRACE_MINUTES),
+    )?;
+
+    let caught_up = if catch_up.unwrap_or(false) {
+        missed::catch_up(&DB, &jobs, &missed)?
+    } else {
+        Vec::new()
+    };
 
-            // 解析 crontab 行
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 6 && current_job_id.is_some() {
-                let schedule = parts[0..5].join(" ");
-                let joined_parts = parts[5..].join(" ");
-                
-                // 提取实际命令
-                let cmd = joined_parts
-                    .split('>')  // 分割重定向
-                    .next()      // 获取命令部分
-                    .unwrap_or("")
-                    .trim();
-                
-                if let Some(job_id) = current_job_id.as_ref() {
-                    // 记录在系统 crontab 中找到的任务 ID
-                    crontab_job_ids.insert(job_id.clone());
-
-                    // 检查数据库中是否已存在此任务
-                    if let Some(existing_job) = db_jobs.iter_mut().find(|j| &j.id == job_id) {
-                        // 更新现有任务
-                        existing_job.schedule = schedule;
-                        existing_job.name = current_job_name.clone();
-                        existing_job.command = cmd.to_string();
-                        existing_job.is_active = true;
-                        DB.update_job(&existing_job)
-                            .map_err(|e| Error::DatabaseError(e.to_string()))?;
-                    } else {
-                        // 创建新任务
-                        let job = CronJob {
-                            id: job_id.clone(),
-                            name: current_job_name.clone(),
-                            schedule,
-                            command: cmd.to_string(),
-                            is_active: true,
-                        };
-                        DB.add_job(&job)
-                            .map_err(|e| Error::DatabaseError(e.to_string()))?;
-                        db_jobs.push(job);
-                    }
-                }
-            }
-        }
+    Ok(MissedRunReport { checked_from, checked_to, missed, caught_up })
+}
+
+fn load_api_settings() -> Result<ApiServerSettings, Error> {
+    let settings = DB.get_setting(api::SETTINGS_KEY)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    Ok(settings.unwrap_or_default())
+}
+
+// 按设置停止并重新启动本地 HTTP 接口服务
+fn restart_api_server(settings: &ApiServerSettings) -> Result<(), Error> {
+    let mut server = API_SERVER.lock().unwrap();
+    if let Some(running) = server.take() {
+        running.stop();
+    }
+    if settings.enabled {
+        *server = Some(ApiServer::start(settings)?);
     }
+    Ok(())
+}
 
-    // 更新数据库中存在但系统 crontab 中不存在的任务状态
-    for job in db_jobs {
-        if !crontab_job_ids.contains(&job.id) && job.is_active {
-            let mut job = job.clone();
-            job.is_active = false;
-            DB.update_job(&job)
-                .map_err(|e| Error::DatabaseError(e.to_string()))?;
-        }
+#[tauri::command]
+async fn get_api_server_settings() -> Result<ApiServerSettings, Error> {
+    let settings = load_api_settings()?;
+    // 首次读取时保存生成的令牌，保证界面展示的令牌与开启后使用的一致
+    DB.set_setting(api::SETTINGS_KEY, &settings)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    Ok(settings)
+}
+
+// 修改接口设置并立即生效，`regenerate_token` 时更换令牌，旧令牌随即失效
+#[tauri::command]
+async fn update_api_server_settings(
+    enabled: bool,
+    port: Option<u16>,
+    regenerate_token: Option<bool>,
+) -> Result<ApiServerSettings, Error> {
+    let mut settings = load_api_settings()?;
+    settings.enabled = enabled;
+    if let Some(port) = port {
+        settings.port = port;
+    }
+    if regenerate_token.unwrap_or(false) {
+        settings.token = api::generate_token();
+    }
+
+    restart_api_server(&settings)?;
+    DB.set_setting(api::SETTINGS_KEY, &settings)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    Ok(settings)
+}
+
+#[tauri::command]
+async fn get_dependency_graph() -> Result<DependencyGraph, Error> {
+    let jobs = DB.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    Ok(deps::build_graph(&jobs))
+}
+
+// 添加初始化函数
+fn initialize_app() -> Result<(), Error> {
+    // 确保数据库已初始化
+    lazy_static::initialize(&DB);
+
+    // 初始化 Python 虚拟环境
+    PYTHON_ENV.initialize()?;
+
+    // 以数据库为准更新 crontab，与数据库不一致的条目保留原样，由用户在漂移报告中处理
+    sync_crontab(&[])?;
+
+    // 开启了本地 HTTP 接口时启动服务，启动失败不影响界面使用
+    if let Err(e) = load_api_settings().and_then(|settings| restart_api_server(&settings)) {
+        eprintln!("Failed to start API server: {}", e);
     }
 
+    // 按补执行策略补执行错过的任务，放在后台线程避免阻塞界面启动
+    std::thread::spawn(|| {
+        if let Err(e) = missed::run_catch_up_policies(&DB) {
+            eprintln!("Failed to catch up missed runs: {}", e);
+        }
+    });
+
     Ok(())
 }
 
@@ -608,17 +884,139 @@ async fn test_cron_job(command: String) -> Result<String, Error> {
     }
 }
 
+// 读取日志末尾一段，大日志只返回最后 64 KB，完整内容通过 `read_job_log` 按范围读取
 #[tauri::command]
 async fn get_job_logs(job_id: String) -> Result<String, Error> {
-    let home = std::env::var("HOME").unwrap_or_default();
-    let log_file = PathBuf::from(home).join(".crontab").join(format!("{}.log", job_id));
-
-    if !log_file.exists() {
+    let log_store = LogStore::default();
+    let chunk = log_store.read_tail(&job_id, None, logs::DEFAULT_TAIL_BYTES)?;
+    if chunk.size == 0 && !log_store.log_info(&job_id)?.exists {
         return Ok("暂无日志".to_string());
     }
 
-    std::fs::read_to_string(&log_file)
-        .map_err(|e| Error::CrontabError(format!("读取日志失败: {}", e)))
+    if chunk.truncated {
+        Ok(format!("……（日志共 {} 字节，仅显示最后 {} 字节）\n{}", chunk.size, chunk.size - chunk.offset, chunk.data))
+    } else {
+        Ok(chunk.data)
+    }
+}
+
+// 按范围读取日志：开头、末尾或指定位置，压缩的日志自动解压
+#[tauri::command]
+async fn read_job_log(job_id: String, range: LogRange) -> Result<LogChunk, Error> {
+    LogStore::default().read_range(&job_id, &range)
+}
+
+#[tauri::command]
+async fn get_job_log_info(job_id: String) -> Result<LogInfo, Error> {
+    LogStore::default().log_info(&job_id)
+}
+
+#[tauri::command]
+async fn get_log_settings() -> Result<LogSettings, Error> {
+    let settings = DB.get_setting(logs::SETTINGS_KEY)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    Ok(settings.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn update_log_settings(settings: LogSettings) -> Result<(), Error> {
+    DB.set_setting(logs::SETTINGS_KEY, &settings)
+        .map_err(|e| Error::DatabaseError(e.to_string()))
+}
+
+// 读取 `run_id` 那次执行（未指定时为最近一次执行）的日志中 `offset` 之后的内容；
+// 未指定位置时只读取该次执行的日志末尾一段，避免一次读取过大的日志
+#[tauri::command]
+async fn tail_job_log(
+    job_id: String,
+    run_id: Option<String>,
+    offset: Option<u64>,
+    max_bytes: Option<u64>,
+) -> Result<LogChunk, Error> {
+    let log_store = LogStore::default();
+    match offset {
+        Some(offset) => log_store.read_from(&job_id, run_id.as_deref(), offset, max_bytes.unwrap_or(logs::MAX_CHUNK_BYTES)),
+        None => log_store.read_tail(&job_id, run_id.as_deref(), max_bytes.unwrap_or(logs::DEFAULT_TAIL_BYTES)),
+    }
+}
+
+// 日志有新内容时发送给前端的事件
+const JOB_LOG_APPENDED_EVENT: &str = "job-log-appended";
+
+// 实时查看日志的轮询间隔
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// 开始实时查看任务日志：返回首段内容，之后同一次执行追加的内容通过 `job-log-appended` 事件发送，
+// 该次执行结束且内容全部发送后停止（最后一段带有 `finished`）。
+// 前端断开后可以用最后收到的 `run_id` 与 `next_offset` 重新调用以继续读取
+#[tauri::command]
+async fn follow_job_log(
+    app: tauri::AppHandle,
+    job_id: String,
+    run_id: Option<String>,
+    offset: Option<u64>,
+) -> Result<LogChunk, Error> {
+    let initial = tail_job_log(job_id.clone(), run_id, offset, None).await?;
+    if initial.finished && initial.next_offset >= initial.size {
+        return Ok(initial);
+    }
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    if let Some(previous) = LOG_FOLLOWERS.lock().unwrap().insert(job_id.clone(), Arc::clone(&stopped)) {
+        previous.store(true, Ordering::Relaxed);
+    }
+
+    let mut run_id = initial.run_id.clone();
+    let mut next_offset = initial.next_offset;
+    let log_store = LogStore::default();
+    std::thread::spawn(move || {
+        while !stopped.load(Ordering::Relaxed) {
+            std::thread::sleep(LOG_POLL_INTERVAL);
+
+            let chunk = match log_store.read_from(&job_id, run_id.as_deref(), next_offset, logs::MAX_CHUNK_BYTES) {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    eprintln!("Failed to follow log of {}: {}", job_id, e);
+                    continue;
+                }
+            };
+            let done = chunk.finished && chunk.next_offset >= chunk.size;
+            if chunk.data.is_empty() && !chunk.reset && !done {
+                continue;
+            }
+            run_id = chunk.run_id.clone();
+            next_offset = chunk.next_offset;
+            if let Err(e) = app.emit_all(JOB_LOG_APPENDED_EVENT, chunk) {
+                eprintln!("Failed to emit log chunk: {}", e);
+            }
+            if done {
+                break;
+            }
+        }
+
+        // 仍是当前的跟踪者时才从表中移除，避免移除同一任务新开始的跟踪
+        let mut followers = LOG_FOLLOWERS.lock().unwrap();
+        if followers.get(&job_id).is_some_and(|current| Arc::ptr_eq(current, &stopped)) {
+            followers.remove(&job_id);
+        }
+    });
+
+    Ok(initial)
+}
+
+#[tauri::command]
+async fn unfollow_job_log(job_id: String) -> Result<(), Error> {
+    if let Some(stopped) = LOG_FOLLOWERS.lock().unwrap().remove(&job_id) {
+        stopped.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+// 停止所有实时查看日志的轮询，窗口关闭时调用
+fn stop_log_followers() {
+    for (_, stopped) in LOG_FOLLOWERS.lock().unwrap().drain() {
+        stopped.store(true, Ordering::Relaxed);
+    }
 }
 
 #[tauri::command]
@@ -649,166 +1047,15 @@ async fn get_job_history_logs(_job_id: String) -> Result<Vec<String>, Error> {
 
 #[tauri::command]
 async fn get_next_runs(schedule: String) -> Result<Vec<String>, Error> {
-    // 如果表达式为空，返回错误
-    if schedule.trim().is_empty() {
-        return Err(Error::CrontabError("Cron 表达式不能为空".to_string()));
-    }
-
-    // 标准化表达式，确保字段数量正确
-    let parts: Vec<&str> = schedule.split_whitespace().collect();
-    if parts.len() != 5 {
-        return Err(Error::CrontabError("Cron 表达式必须包含5个字段：分钟 小时 日期 月份 星期".to_string()));
-    }
-
-    // 构建完整的 cron 表达式（添加秒字段）
-    let full_schedule = format!("0 {}", schedule);
-
-    // 处理 */n 格式
-    if schedule.contains("*/") {
-        let now = Local::now();
-        let mut next = now
-            .with_second(0)  // 总是从0秒开始
-            .unwrap();
-
-        let mut dates = Vec::new();
-        
-        for _ in 0..5 {
-            // 根据表达式计算下一个执行时间
-            for (i, part) in parts.iter().enumerate() {
-                if part.starts_with("*/") {
-                    if let Ok(interval) = part.trim_start_matches("*/").parse::<u32>() {
-                        match i {
-                            0 => { // 分钟
-                                // 如果是 * 则保持当前分钟，否则设置为0
-                                if part == &"*" {
-                                    // 保持当前分钟
-                                } else {
-                                    next = next.with_minute(0).unwrap();
-                                }
-                            },
-                            1 => { // 小时
-                                let current_hour = next.hour();
-                                let next_hour = ((current_hour / interval) + 1) * interval;
-                                if next_hour >= 24 {
-                                    next = next + chrono::Duration::days(1);
-                                    next = next.with_hour(0).unwrap();
-                                } else {
-                                    next = next.with_hour(next_hour).unwrap();
-                                }
-                            },
-                            _ => {} // 其他字段暂时保持不变
-                        }
-                    }
-                }
-            }
-            
-            dates.push(next.format("%Y-%m-%d %H:%M:%S").to_string());
-            next = next + chrono::Duration::hours(1);  // 每次增加一小时
-        }
-        
-        return Ok(dates);
-    }
-
-    // 处理特殊的 cron 表达式
-    if schedule.starts_with('@') {
-        let now = Local::now();
-        let next_runs = match schedule.as_str() {
-            "@yearly" | "@annually" => {
-                let mut dates = Vec::new();
-                let mut next = now.with_month(1).unwrap()
-                    .with_day(1).unwrap()
-                    .with_hour(0).unwrap()
-                    .with_minute(0).unwrap()
-                    .with_second(0).unwrap();
-                if next <= now {
-                    next = next.with_year(next.year() + 1).unwrap();
-                }
-                for _ in 0..5 {
-                    dates.push(next.format("%Y-%m-%d %H:%M:%S").to_string());
-                    next = next.with_year(next.year() + 1).unwrap();
-                }
-                dates
-            },
-            "@monthly" => {
-                let mut dates = Vec::new();
-                let mut next = now.with_day(1).unwrap()
-                    .with_hour(0).unwrap()
-                    .with_minute(0).unwrap()
-                    .with_second(0).unwrap();
-                if next <= now {
-                    next = next.with_month(next.month() + 1).unwrap();
-                }
-                for _ in 0..5 {
-                    dates.push(next.format("%Y-%m-%d %H:%M:%S").to_string());
-                    next = next.with_month(next.month() + 1).unwrap();
-                }
-                dates
-            },
-            "@weekly" => {
-                let mut dates = Vec::new();
-                let days_until_sunday = (7 - now.weekday().num_days_from_sunday()) % 7;
-                let mut next = now + chrono::Duration::days(days_until_sunday as i64);
-                next = next.with_hour(0).unwrap()
-                    .with_minute(0).unwrap()
-                    .with_second(0).unwrap();
-                if next <= now {
-                    next = next + chrono::Duration::days(7);
-                }
-                for _ in 0..5 {
-                    dates.push(next.format("%Y-%m-%d %H:%M:%S").to_string());
-                    next = next + chrono::Duration::days(7);
-                }
-                dates
-            },
-            "@daily" | "@midnight" => {
-                let mut dates = Vec::new();
-                let mut next = now.with_hour(0).unwrap()
-                    .with_minute(0).unwrap()
-                    .with_second(0).unwrap();
-                if next <= now {
-                    next = next + chrono::Duration::days(1);
-                }
-                for _ in 0..5 {
-                    dates.push(next.format("%Y-%m-%d %H:%M:%S").to_string());
-                    next = next + chrono::Duration::days(1);
-                }
-                dates
-            },
-            "@hourly" => {
-                let mut dates = Vec::new();
-                let mut next = now.with_minute(0).unwrap()
-                    .with_second(0).unwrap();
-                if next <= now {
-                    next = next + chrono::Duration::hours(1);
-                }
-                for _ in 0..5 {
-                    dates.push(next.format("%Y-%m-%d %H:%M:%S").to_string());
-                    next = next + chrono::Duration::hours(1);
-                }
-                dates
-            },
-            _ => return Err(Error::CrontabError(format!("不支持的特殊表达式: {}", schedule)))
-        };
-        return Ok(next_runs);
-    }
-
-    // 处理标准 cron 表达式
-    let parsed_schedule = Schedule::from_str(&full_schedule)
-        .map_err(|e| Error::CrontabError(format!("无效的 cron 表达式 '{}': {}", &schedule, e)))?;
-    
-    let next_runs: Vec<String> = parsed_schedule
-        .upcoming(Local)
-        .take(5)
-        .map(|datetime| {
-            datetime.format("%Y-%m-%d %H:%M:%S").to_string()
-        })
-        .collect();
-
+    let next_runs = schedule::next_fire_times(&schedule, 5)?;
     if next_runs.is_empty() {
-        return Err(Error::CrontabError("无法计算下次执行时间".to_string()));
+        return Err(Error::InvalidCronExpression("该表达式没有固定的执行时间".to_string()));
     }
 
-    Ok(next_runs)
+    Ok(next_runs
+        .iter()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .collect())
 }
 
 fn main() {
@@ -818,19 +1065,68 @@ fn main() {
     }
 
     tauri::Builder::default()
+        .setup(|app| {
+            spawn_crontab_watcher(app.handle());
+            desktop_notify::spawn_failure_watcher(app.handle(), Arc::clone(&DB));
+            Ok(())
+        })
+        .on_window_event(|event| {
+            if let tauri::WindowEvent::Destroyed = event.event() {
+                stop_log_followers();
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             get_cron_jobs,
+            get_tags,
+            set_job_tags,
+            set_tag_active,
+            delete_tag_jobs,
             add_cron_job,
             toggle_cron_job,
             delete_cron_job,
             update_cron_job,
+            run_cron_job,
+            test_webhook,
+            get_smtp_settings,
+            update_smtp_settings,
+            test_smtp,
+            get_desktop_notification_settings,
+            update_desktop_notification_settings,
+            set_job_notifications_muted,
             get_next_runs,
+            get_crontab_drift,
+            resolve_crontab_drift,
+            get_crontab_variables,
+            set_crontab_variables,
+            export_jobs,
+            import_jobs,
+            export_crontab,
+            import_crontab_file,
             get_job_history,
-            record_job_history,
+            query_job_history,
+            search_logs,
+            get_history_output,
+            get_capture_settings,
+            update_capture_settings,
+            get_job_aggregates,
+            get_job_stats,
+            check_missed_runs,
+            get_dependency_graph,
+            get_api_server_settings,
+            update_api_server_settings,
             test_cron_job,
             get_job_logs,
+            tail_job_log,
+            read_job_log,
+            get_job_log_info,
+            get_log_settings,
+            update_log_settings,
+            follow_job_log,
+            unfollow_job_log,
             get_job_history_logs,
-            check_permissions
+            check_permissions,
+            check_database,
+            repair_database
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
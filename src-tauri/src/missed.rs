@@ -0,0 +1,248 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use crate::db::Database;
+use crate::logs::LogStore;
+use crate::{runner, schedule, CronJob, Error, JobHistory, RunTrigger};
+
+/// 执行记录相对计划时间允许的延迟，超过后视为错过
+pub const DEFAULT_GRACE_MINUTES: i64 = 5;
+
+/// 未设置最大延迟时，补执行只覆盖最近一天内错过的执行
+pub const DEFAULT_MAX_LATENESS_MINUTES: u32 = 24 * 60;
+
+/// 执行记录早于计划时间的容差，用于吸收 cron 与 runner 之间的时钟误差
+const EARLY_TOLERANCE_SECONDS: i64 = 60;
+
+/// 一次错过的计划执行
+#[derive(Debug, Serialize, Clone)]
+pub struct MissedRun {
+    pub job_id: String,
+    pub job_name: Option<String>,
+    pub scheduled_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct MissedRunReport {
+    pub checked_from: DateTime<Utc>,
+    pub checked_to: DateTime<Utc>,
+    pub missed: Vec<MissedRun>,
+    /// 补执行产生的执行记录
+    pub caught_up: Vec<JobHistory>,
+}
+
+/// 比较启用中任务在 `[from, to)` 区间内的计划执行时间与按计划触发的执行记录，找出没有对应记录的计划执行。
+/// 手动执行、补执行等不代替计划执行；计划时间距 `to` 不足 `grace` 的执行可能仍在进行，不计入结果。
+/// 执行记录在执行结束后才写入，仍在执行中的执行（日志以 `.part` 结尾）按其开始时间同样算作已执行，
+/// 执行时间超过 `grace` 的任务不会被当作错过而重复启动
+pub fn find_missed_runs(
+    db: &Database,
+    logs: &LogStore,
+    jobs: &[CronJob],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    grace: Duration,
+) -> Result<Vec<MissedRun>, Error> {
+    let mut missed = Vec::new();
+
+    for job in jobs.iter().filter(|job| job.is_active) {
+        // 任务创建或最近一次启用之前的计划时间不算错过
+        let active_since = db.get_job_active_since(&job.id)
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        let start = active_since.map_or(from, |active_since| active_since.max(from));
+        let end = to - grace;
+        if end <= start {
+            continue;
+        }
+
+        let mut fire_times = schedule::fire_times_between(&job.schedule, start, end)?.peekable();
+        if fire_times.peek().is_none() {
+            continue;
+        }
+
+        let runs_from = start - Duration::seconds(EARLY_TOLERANCE_SECONDS);
+        let mut runs = db.get_scheduled_run_times(&job.id, runs_from, to)
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        // 执行结束时先写入执行记录再结束日志，同一次执行可能同时出现在两处，按开始时间去重
+        runs.extend(logs.running_since(&job.id)?.into_iter().filter(|started| *started >= runs_from && *started < to));
+        runs.sort();
+        runs.dedup();
+
+        // 计划时间与执行时间都已排序，每条执行记录最多匹配一次计划执行
+        let mut runs = runs.into_iter().peekable();
+        for scheduled_time in fire_times {
+            while runs
+                .next_if(|run| *run < scheduled_time - Duration::seconds(EARLY_TOLERANCE_SECONDS))
+                .is_some()
+            {}
+
+            if runs.next_if(|run| *run <= scheduled_time + grace).is_none() {
+                missed.push(MissedRun {
+                    job_id: job.id.clone(),
+                    job_name: job.name.clone(),
+                    scheduled_time,
+                });
+            }
+        }
+    }
+
+    Ok(missed)
+}
+
+/// 像 anacron 一样补执行：每个错过执行的任务只补执行一次
+pub fn catch_up(db: &Database, jobs: &[CronJob], missed: &[MissedRun]) -> Result<Vec<JobHistory>, Error> {
+    let mut histories = Vec::new();
+
+    for job in jobs {
+        if missed.iter().any(|run| run.job_id == job.id) {
+            histories.extend(runner::run_chain(db, job, RunTrigger::CatchUp)?);
+        }
+    }
+
+    Ok(histories)
+}
+
+/// 按任务的补执行策略处理错过的执行：最大延迟内错过了计划执行的任务补执行一次。
+/// 同一次错过的执行通过数据库登记去重，GUI 与多个 runner 进程同时检查时也只会补执行一次
+pub fn run_catch_up_policies(db: &Database) -> Result<Vec<JobHistory>, Error> {
+    let jobs: Vec<CronJob> = db.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .into_iter()
+        .filter(|job| job.is_active && job.catch_up)
+        .collect();
+
+    let now = Utc::now();
+    let mut histories = Vec::new();
+
+    for job in &jobs {
+        let max_lateness = job.max_lateness_minutes.unwrap_or(DEFAULT_MAX_LATENESS_MINUTES);
+        let missed = find_missed_runs(
+            db,
+            &LogStore::default(),
+            std::slice::from_ref(job),
+            now - Duration::minutes(max_lateness as i64),
+            now,
+            Duration::minutes(DEFAULT_GRACE_MINUTES),
+        )?;
+
+        // 只补执行最近一次错过的执行
+        let Some(latest) = missed.last() else {
+            continue;
+        };
+
+        let claimed = db.claim_catch_up(&job.id, latest.scheduled_time)
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        if claimed {
+            histories.extend(runner::run_chain(db, job, RunTrigger::CatchUp)?);
+        }
+    }
+
+    Ok(histories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+    use crate::logs::LogSettings;
+    use crate::{JobStatus, TempDir};
+
+    fn job() -> CronJob {
+        CronJob {
+            id: "job-1".to_string(),
+            schedule: "0 * * * *".to_string(),
+            command: "true".to_string(),
+            is_active: true,
+            ..CronJob::default()
+        }
+    }
+
+    fn history(id: &str, execution_time: DateTime<Utc>, trigger: RunTrigger) -> JobHistory {
+        JobHistory {
+            id: id.to_string(),
+            job_id: "job-1".to_string(),
+            execution_time,
+            status: JobStatus::Success,
+            output: String::new(),
+            duration_ms: None,
+            exit_code: Some(0),
+            trigger,
+            matched_rule: None,
+            stdout: None,
+            stderr: None,
+            omitted_bytes: 0,
+        }
+    }
+
+    /// 计划表达式按本地时间计算，测试的时间点同样从本地时间构造，在任意时区下都落在整点
+    fn local(hour: u32, minute: u32) -> DateTime<Utc> {
+        Local.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap().with_timezone(&Utc)
+    }
+
+    fn setup() -> (TempDir, Database, LogStore, CronJob) {
+        let dir = TempDir::new();
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let logs = LogStore::new(dir.path().join("logs"));
+        let job = job();
+        db.add_job(&job).unwrap();
+        rusqlite::Connection::open(dir.path().join("crontab.db"))
+            .unwrap()
+            .execute("UPDATE cron_jobs SET created_at = '2000-01-01 00:00:00'", [])
+            .unwrap();
+        (dir, db, logs, job)
+    }
+
+    fn grace() -> Duration {
+        Duration::minutes(DEFAULT_GRACE_MINUTES)
+    }
+
+    #[test]
+    fn only_scheduled_runs_count_against_fire_times() {
+        let (_dir, db, logs, job) = setup();
+        // 00:00 由计划触发，01:00 只有手动执行
+        db.add_history(&history("run-1", local(0, 0) + Duration::seconds(3), RunTrigger::Schedule)).unwrap();
+        db.add_history(&history("run-2", local(1, 0), RunTrigger::Manual)).unwrap();
+
+        let missed = find_missed_runs(&db, &logs, &[job], local(0, 0), local(2, 0), grace()).unwrap();
+        let missed: Vec<_> = missed.iter().map(|run| run.scheduled_time).collect();
+        assert_eq!(missed, [local(1, 0)]);
+    }
+
+    #[test]
+    fn runs_before_the_last_enable_are_not_missed() {
+        let (dir, db, logs, job) = setup();
+        let conn = rusqlite::Connection::open(dir.path().join("crontab.db")).unwrap();
+
+        // 停用后重新启用会记录启用时间
+        db.set_jobs_active(std::slice::from_ref(&job.id), false).unwrap();
+        db.set_jobs_active(std::slice::from_ref(&job.id), true).unwrap();
+        let active_since = db.get_job_active_since(&job.id).unwrap().unwrap();
+        assert!(active_since > Utc::now() - Duration::minutes(1));
+
+        let enabled_at = local(1, 30).format("%Y-%m-%d %H:%M:%S").to_string();
+        conn.execute("UPDATE cron_jobs SET enabled_at = ?1", [enabled_at]).unwrap();
+        let missed = find_missed_runs(&db, &logs, &[job], local(0, 0), local(3, 0), grace()).unwrap();
+        let missed: Vec<_> = missed.iter().map(|run| run.scheduled_time).collect();
+        assert_eq!(missed, [local(2, 0)]);
+    }
+
+    #[test]
+    fn long_running_jobs_are_not_missed_while_they_run() {
+        let (_dir, db, logs, job) = setup();
+        // 01:00 的执行已持续 40 分钟，还没有执行记录
+        let started = local(1, 0) + Duration::seconds(2);
+        let (log_path, _) = logs.create_run_log(&job.id, "run-1", started).unwrap();
+
+        let missed = find_missed_runs(&db, &logs, std::slice::from_ref(&job), local(0, 30), local(1, 40), grace()).unwrap();
+        assert!(missed.is_empty());
+
+        // 结束时执行记录与执行中的日志短暂并存，结果不变
+        db.add_history(&history("run-1", started, RunTrigger::Schedule)).unwrap();
+        let missed = find_missed_runs(&db, &logs, std::slice::from_ref(&job), local(0, 30), local(2, 10), grace()).unwrap();
+        let missed: Vec<_> = missed.iter().map(|run| run.scheduled_time).collect();
+        assert_eq!(missed, [local(2, 0)]);
+
+        logs.finish_run_log(&log_path, &LogSettings::default()).unwrap();
+        let missed = find_missed_runs(&db, &logs, &[job], local(0, 30), local(1, 40), grace()).unwrap();
+        assert!(missed.is_empty());
+    }
+}
@@ -0,0 +1,342 @@
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::db::Database;
+use crate::email::{self, SmtpSettings};
+use crate::{log_dir, runner, CronJob, Error, JobHistory, JobStatus, NotifyEvent, RunTrigger, WebhookRule};
+
+/// 发送失败时的最多尝试次数
+const MAX_ATTEMPTS: u32 = 3;
+
+/// 第一次重试前的等待时间，之后每次翻倍
+const RETRY_DELAY: Duration = if cfg!(test) { Duration::from_millis(10) } else { Duration::from_secs(2) };
+
+/// 单次请求的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 通知中附带的日志末尾行数
+pub const LOG_TAIL_LINES: usize = 50;
+
+/// POST 到 Webhook 地址的内容
+#[derive(Debug, Serialize, Clone)]
+pub struct WebhookPayload {
+    /// 触发通知的规则，测试发送时为空
+    pub event: Option<NotifyEvent>,
+    pub job_id: String,
+    pub job_name: Option<String>,
+    pub status: JobStatus,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub execution_time: DateTime<Utc>,
+    pub trigger: RunTrigger,
+    pub log_tail: String,
+}
+
+impl WebhookPayload {
+    pub fn new(job: &CronJob, history: &JobHistory, event: Option<NotifyEvent>) -> Self {
+        WebhookPayload {
+            event,
+            job_id: job.id.clone(),
+            job_name: job.name.clone(),
+            status: history.status.clone(),
+            exit_code: history.exit_code,
+            duration_ms: history.duration_ms,
+            execution_time: history.execution_time,
+            trigger: history.trigger.clone(),
+            log_tail: log_tail(&history.output, LOG_TAIL_LINES),
+        }
+    }
+}
+
+/// 输出的最后 `lines` 行
+pub fn log_tail(output: &str, lines: usize) -> String {
+    let all: Vec<&str> = output.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+/// 截至最近一次执行的连续失败次数，`statuses` 中最近的在前
+fn failure_streak(statuses: &[JobStatus]) -> u32 {
+    statuses
+        .iter()
+        .take_while(|status| matches!(status, JobStatus::Failed))
+        .count() as u32
+}
+
+/// 本次执行结果是否满足通知时机。`statuses` 为包括本次在内的最近执行状态，最近的在前
+fn should_notify(event: &NotifyEvent, statuses: &[JobStatus]) -> bool {
+    match (event, statuses) {
+        (NotifyEvent::OnFailure, [JobStatus::Failed, ..]) => true,
+        (NotifyEvent::OnSuccess, [JobStatus::Success, ..]) => true,
+        (NotifyEvent::OnRecovery, [JobStatus::Success, JobStatus::Failed, ..]) => true,
+        // 只在达到次数时发送一次，之后继续失败不再重复发送
+        (NotifyEvent::OnConsecutiveFailures(count), _) => failure_streak(statuses) == *count,
+        _ => false,
+    }
+}
+
+/// 发送一次 Webhook，网络错误、5xx 与 429 按指数退避重试，其他错误状态码直接返回
+pub fn send_webhook(url: &str, payload: &WebhookPayload) -> Result<(), Error> {
+    let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 1;
+
+    loop {
+        let error = match agent.post(url).send_json(payload) {
+            Ok(_) => return Ok(()),
+            Err(ureq::Error::Status(code, _)) if code != 429 && code < 500 => {
+                return Err(Error::NotificationError(format!("{} 返回状态码 {}", url, code)));
+            }
+            Err(e) => e,
+        };
+
+        if attempt >= MAX_ATTEMPTS {
+            return Err(Error::NotificationError(format!(
+                "发送到 {} 失败（已尝试 {} 次）: {}",
+                url, attempt, error
+            )));
+        }
+
+        std::thread::sleep(delay);
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// 本次执行是否有需要发送的通知
+fn has_notifications(job: &CronJob, history: &JobHistory) -> bool {
+    !job.webhooks.is_empty() || (matches!(history.status, JobStatus::Failed) && !job.email_recipients.is_empty())
+}
+
+/// 在后台发送本次执行的通知，不阻塞任务执行与下游任务的触发。
+/// 通知由独立的 `crontab-ui-runner --notify <history_id>` 进程发送，runner 进程退出后仍会继续重试；
+/// runner 尚未安装时在当前进程的后台线程中发送
+pub fn dispatch(job: &CronJob, history: &JobHistory) {
+    if !has_notifications(job, history) {
+        return;
+    }
+
+    match spawn_notifier(&history.id) {
+        Ok(()) => {}
+        Err(e) => {
+            eprintln!("启动通知进程失败，在后台线程中发送: {}", e);
+            let history_id = history.id.clone();
+            std::thread::spawn(move || {
+                let result = crate::data_dir()
+                    .ok_or_else(|| Error::NotificationError("无法获取应用数据目录".to_string()))
+                    .and_then(|dir| Database::new(dir).map_err(|e| Error::DatabaseError(e.to_string())))
+                    .and_then(|db| notify_history(&db, &history_id));
+                if let Err(e) = result {
+                    eprintln!("发送执行记录 {} 的通知失败: {}", history_id, e);
+                }
+            });
+        }
+    }
+}
+
+fn spawn_notifier(history_id: &str) -> Result<(), Error> {
+    let runner = runner::runner_path()?;
+    if !runner.is_file() {
+        return Err(Error::RunnerUnavailable(format!("{} 不存在", runner.display())));
+    }
+
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_dir().join("runner.log"))
+        .map_err(|e| Error::NotificationError(format!("打开 runner.log 失败: {}", e)))?;
+    let stderr = log.try_clone()
+        .map_err(|e| Error::NotificationError(format!("打开 runner.log 失败: {}", e)))?;
+    Command::new(runner)
+        .arg(runner::NOTIFY_FLAG)
+        .arg(history_id)
+        .stdin(Stdio::null())
+        .stdout(log)
+        .stderr(stderr)
+        .spawn()
+        .map_err(|e| Error::NotificationError(e.to_string()))?;
+    Ok(())
+}
+
+/// 按 ID 读取执行记录与任务并发送通知，由 `crontab-ui-runner --notify` 调用
+pub fn notify_history(db: &Database, history_id: &str) -> Result<(), Error> {
+    let history = db.get_history(history_id)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .ok_or_else(|| Error::NotificationError(format!("执行记录 {} 不存在", history_id)))?;
+    let job = db.get_job(&history.job_id)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .ok_or(Error::JobNotFound)?;
+    notify_run(db, &job, &history)
+}
+
+/// 按任务的通知规则发送本次执行结果，执行失败时同时发送邮件。
+/// 所有通知都会尝试发送，返回第一个发送失败的错误
+pub fn notify_run(db: &Database, job: &CronJob, history: &JobHistory) -> Result<(), Error> {
+    let mut first_error = None;
+    if let Err(e) = notify_webhooks(db, job, history) {
+        first_error.get_or_insert(e);
+    }
+    if matches!(history.status, JobStatus::Failed) && !job.email_recipients.is_empty() {
+        if let Err(e) = notify_email(db, job, history) {
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+fn notify_email(db: &Database, job: &CronJob, history: &JobHistory) -> Result<(), Error> {
+    let settings: SmtpSettings = db.get_setting(email::SETTINGS_KEY)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .unwrap_or_default();
+    if !settings.enabled {
+        return Ok(());
+    }
+
+    let payload = WebhookPayload::new(job, history, Some(NotifyEvent::OnFailure));
+    email::send_email(&settings, &job.email_recipients, &payload)
+}
+
+fn notify_webhooks(db: &Database, job: &CronJob, history: &JobHistory) -> Result<(), Error> {
+    if job.webhooks.is_empty() {
+        return Ok(());
+    }
+
+    // 需要的历史长度：判断恢复需要前一次状态，连续失败需要比阈值多一次
+    let depth = job.webhooks
+        .iter()
+        .map(|rule| match rule.event {
+            NotifyEvent::OnConsecutiveFailures(count) => count.saturating_add(1),
+            _ => 2,
+        })
+        .max()
+        .unwrap_or(2);
+    let statuses = db.get_recent_statuses(&job.id, &history.execution_time, depth)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+    let mut first_error = None;
+    for WebhookRule { url, event } in &job.webhooks {
+        if !should_notify(event, &statuses) {
+            continue;
+        }
+        let payload = WebhookPayload::new(job, history, Some(event.clone()));
+        if let Err(e) = send_webhook(url, &payload) {
+            first_error.get_or_insert(e);
+        }
+    }
+
+    first_error.map_or(Ok(()), Err)
+}
+
+/// 测试发送使用的示例执行记录
+pub fn sample_history(job_id: &str) -> JobHistory {
+    JobHistory {
+        id: String::new(),
+        job_id: job_id.to_string(),
+        execution_time: Utc::now(),
+        status: JobStatus::Success,
+        output: "这是一条测试通知".to_string(),
+        duration_ms: Some(0),
+        exit_code: Some(0),
+        trigger: RunTrigger::Manual,
+        matched_rule: None,
+        stdout: None,
+        stderr: None,
+        omitted_bytes: 0,
+    }
+}
+
+/// 向指定地址发送一条测试通知，有执行记录时使用最近一次执行结果
+pub fn send_test(url: &str, job: &CronJob, latest: Option<&JobHistory>) -> Result<(), Error> {
+    let sample = sample_history(&job.id);
+    let payload = WebhookPayload::new(job, latest.unwrap_or(&sample), None);
+    send_webhook(url.trim(), &payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver};
+    use crate::TempDir;
+
+    /// 本地 Webhook 服务：按顺序返回 `statuses` 中的状态码，收到的请求体通过通道返回
+    fn stub_server(statuses: Vec<u16>) -> (String, Receiver<serde_json::Value>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", server.server_addr().to_ip().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for status in statuses {
+                let Ok(mut request) = server.recv() else { return };
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                sender.send(serde_json::from_str(&body).unwrap()).unwrap();
+                request.respond(tiny_http::Response::empty(status)).unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    fn job(url: &str, event: NotifyEvent) -> CronJob {
+        CronJob {
+            id: "job-1".to_string(),
+            name: Some("备份".to_string()),
+            schedule: "0 * * * *".to_string(),
+            command: "false".to_string(),
+            is_active: true,
+            webhooks: vec![WebhookRule { url: url.to_string(), event }],
+            ..CronJob::default()
+        }
+    }
+
+    fn history(id: &str, status: JobStatus, execution_time: DateTime<Utc>) -> JobHistory {
+        JobHistory {
+            id: id.to_string(),
+            job_id: "job-1".to_string(),
+            execution_time,
+            status,
+            output: "line 1\nline 2".to_string(),
+            duration_ms: Some(5),
+            exit_code: Some(1),
+            trigger: RunTrigger::Schedule,
+            matched_rule: None,
+            stdout: None,
+            stderr: None,
+            omitted_bytes: 0,
+        }
+    }
+
+    #[test]
+    fn retries_server_errors_but_not_client_errors() {
+        let (url, received) = stub_server(vec![500, 503, 200]);
+        let payload = WebhookPayload::new(&job(&url, NotifyEvent::OnFailure), &sample_history("job-1"), None);
+        send_webhook(&url, &payload).unwrap();
+        assert_eq!(received.try_iter().count(), 3);
+
+        let (url, received) = stub_server(vec![404]);
+        assert!(matches!(send_webhook(&url, &payload), Err(Error::NotificationError(_))));
+        assert_eq!(received.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn notifies_from_the_stored_history_ignoring_later_runs() {
+        let dir = TempDir::new();
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let (url, received) = stub_server(vec![200]);
+        db.add_job(&job(&url, NotifyEvent::OnRecovery)).unwrap();
+
+        let now = Utc::now();
+        db.add_history(&history("run-1", JobStatus::Failed, now - chrono::Duration::minutes(2))).unwrap();
+        db.add_history(&history("run-2", JobStatus::Success, now - chrono::Duration::minutes(1))).unwrap();
+        // 通知发送前又有一次成功的执行，不影响 run-2 是否算作恢复
+        db.add_history(&history("run-3", JobStatus::Success, now)).unwrap();
+
+        notify_history(&db, "run-2").unwrap();
+        let body = received.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(body["job_id"], "job-1");
+        assert_eq!(body["event"], "OnRecovery");
+        assert_eq!(body["log_tail"], "line 1\nline 2");
+
+        // run-3 的前一次也是成功，不算恢复，不会发送请求
+        notify_history(&db, "run-3").unwrap();
+        assert!(received.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+use chrono::Utc;
+use uuid::Uuid;
+use crate::db::Database;
+use crate::capture::{self, CaptureSettings, OutputStream};
+use crate::logs::{self, LogStore};
+use crate::{criteria, notify};
+use crate::python::PythonEnv;
+use crate::{log_dir, CronJob, Error, JobHistory, JobStatus, RunTrigger, TriggerCondition};
+
+/// runner 可执行文件名，随主程序一起分发
+pub const RUNNER_BIN_NAME: &str = "crontab-ui-runner";
+
+/// 指定 runner 可执行文件的环境变量，未随主程序一起构建时（例如 `cargo run`）用于指向 runner
+pub const RUNNER_ENV: &str = "CRONTAB_UI_RUNNER";
+
+/// crontab 中使用的 runner 路径：应用数据目录下的 `bin/crontab-ui-runner`。
+/// 主程序所在目录不一定长期存在（例如 AppImage 挂载在 `/tmp/.mount_*`，退出后即卸载），
+/// 因此 runner 会先复制到这个固定位置
+pub fn runner_path() -> Result<PathBuf, Error> {
+    let data_dir = crate::data_dir()
+        .ok_or_else(|| Error::RunnerUnavailable("无法获取应用数据目录".to_string()))?;
+    Ok(data_dir.join("bin").join(RUNNER_BIN_NAME))
+}
+
+/// 随主程序分发的 runner：优先使用 `CRONTAB_UI_RUNNER` 指定的路径，其次是主程序所在目录
+fn bundled_runner() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(RUNNER_ENV) {
+        return Some(PathBuf::from(path)).filter(|path| path.is_file());
+    }
+    std::env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(RUNNER_BIN_NAME))
+        .filter(|path| path.is_file())
+}
+
+/// 把随主程序分发的 runner 安装到固定路径并返回该路径，内容未变化时不重新复制。
+/// 找不到可用的 runner 时返回错误，调用方据此拒绝写入 crontab
+pub fn install_runner() -> Result<PathBuf, Error> {
+    let target = runner_path()?;
+    let Some(source) = bundled_runner() else {
+        if target.is_file() {
+            return Ok(target);
+        }
+        return Err(Error::RunnerUnavailable(format!(
+            "找不到 {}，请先执行 `cargo build --bin {}`，或通过环境变量 {} 指定其路径",
+            RUNNER_BIN_NAME, RUNNER_BIN_NAME, RUNNER_ENV
+        )));
+    };
+
+    let bytes = std::fs::read(&source)
+        .map_err(|e| Error::RunnerUnavailable(format!("读取 {} 失败: {}", source.display(), e)))?;
+    if std::fs::read(&target).is_ok_and(|installed| installed == bytes) {
+        return Ok(target);
+    }
+
+    let install_error = |e: std::io::Error| {
+        Error::RunnerUnavailable(format!("安装 runner 到 {} 失败: {}", target.display(), e))
+    };
+    let dir = target.parent().expect("runner 路径包含 bin 目录");
+    std::fs::create_dir_all(dir).map_err(install_error)?;
+    // 先写入临时文件再改名，避免 cron 恰好在复制过程中执行到不完整的文件
+    let temp = dir.join(format!(".{}.{}", RUNNER_BIN_NAME, std::process::id()));
+    std::fs::write(&temp, &bytes).map_err(install_error)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o755)).map_err(install_error)?;
+    }
+    std::fs::rename(&temp, &target).map_err(install_error)?;
+    Ok(target)
+}
+
+/// 判断 crontab 中解析出的命令是否为 runner 调用
+pub fn is_runner_command(command: &str) -> bool {
+    command.contains(RUNNER_BIN_NAME)
+}
+
+/// runner 打印任务输出的参数，cron 会把输出发送到 `MAILTO`
+pub const PRINT_OUTPUT_FLAG: &str = "--print-output";
+
+/// runner 只发送某次执行的通知的参数：`crontab-ui-runner --notify <history_id>`
+pub const NOTIFY_FLAG: &str = "--notify";
+
+/// runner 只检查并补执行错过的任务的参数
+pub const CATCH_UP_FLAG: &str = "--catch-up";
+
+/// 补执行检查的执行计划，电脑唤醒后几分钟内即可完成补执行
+pub const CATCH_UP_SCHEDULE: &str = "*/5 * * * *";
+
+/// 补执行检查在 crontab 中的命令，与任务的执行分开，任务执行不会因补执行而延长
+pub fn catch_up_command(runner: &Path) -> String {
+    format!(
+        "'{}' {} >> '{}' 2>&1",
+        runner.display(),
+        CATCH_UP_FLAG,
+        log_dir().join("runner.log").display()
+    )
+}
+
+/// 任务在 crontab 中的命令：由 `runner` 执行并记录历史，runner 自身的错误追加到 `runner.log`。
+/// 单独设置了 `MAILTO` 的任务由 runner 打印输出，交给 cron 发送邮件
+pub fn crontab_command(job: &CronJob, runner: &Path) -> String {
+    let log = log_dir().join("runner.log");
+    match job.mailto {
+        Some(_) => format!(
+            "'{}' {} {} 2>> '{}'",
+            runner.display(),
+            job.id,
+            PRINT_OUTPUT_FLAG,
+            log.display()
+        ),
+        None => format!(
+            "'{}' {} >> '{}' 2>&1",
+            runner.display(),
+            job.id,
+            log.display()
+        ),
+    }
+}
+
+/// 执行任务命令，输出边执行边写入任务日志（便于实时查看），结束后按采集设置记录到执行历史
+pub fn run_job(db: &Database, job: &CronJob, trigger: RunTrigger) -> Result<JobHistory, Error> {
+    let home = std::env::var("HOME").unwrap_or_default();
+    let path = std::env::var("PATH").unwrap_or_default();
+    let venv_path = PythonEnv::new(&home)?.get_venv_path();
+
+    // 每次执行单独写一份日志，同一任务的执行时间重叠时互不覆盖
+    let run_id = Uuid::new_v4().to_string();
+    let execution_time = Utc::now();
+    let log_store = LogStore::default();
+    let (log_path, log_file) = log_store.create_run_log(&job.id, &run_id, execution_time)?;
+
+    let settings: CaptureSettings = db.get_setting(capture::SETTINGS_KEY)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?
+        .unwrap_or_default();
+    // 合并采集时与原先 crontab 中的 `2>&1` 一致，stderr 并入 stdout 并保留输出顺序
+    let script = if settings.separate_streams {
+        job.command.clone()
+    } else {
+        format!("exec 2>&1\n{}", job.command)
+    };
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(script)
+        .env("HOME", &home)
+        .env("PATH", format!("{}:{}", path, venv_path.join("bin").display()))
+        .env("VIRTUAL_ENV", venv_path.display().to_string());
+
+    let started = Instant::now();
+    // 成功规则逐行匹配完整输出，不受保存到执行历史的大小上限影响
+    let mut matcher = criteria::OutputMatcher::new(&job.success_rules);
+    let result = capture::run(command, log_file, &settings, |line| {
+        matcher.feed(&String::from_utf8_lossy(line));
+    });
+    let duration_ms = started.elapsed().as_millis() as i64;
+
+    let history = match result {
+        Ok((exit_status, captured)) => {
+            let exit_code = exit_status.code();
+            let (status, matched_rule) = matcher.finish(exit_code);
+            let streams = settings.separate_streams.then(|| {
+                (captured.stream(OutputStream::Stdout), captured.stream(OutputStream::Stderr))
+            });
+            let (stdout, stderr) = streams.unzip();
+            JobHistory {
+                id: run_id,
+                job_id: job.id.clone(),
+                execution_time,
+                status,
+                output: captured.combined(),
+                duration_ms: Some(duration_ms),
+                exit_code,
+                trigger,
+                matched_rule,
+                stdout,
+                stderr,
+                omitted_bytes: captured.omitted_bytes(),
+            }
+        }
+        Err(e) => {
+            let output = format!("执行命令失败: {}", e);
+            std::fs::write(&log_path, &output)
+                .map_err(|e| Error::CrontabError(format!("写入日志失败: {}", e)))?;
+            JobHistory {
+                id: run_id,
+                job_id: job.id.clone(),
+                execution_time,
+                status: JobStatus::Failed,
+                output,
+                duration_ms: Some(duration_ms),
+                exit_code: None,
+                trigger,
+                matched_rule: None,
+                stdout: None,
+                stderr: None,
+                omitted_bytes: 0,
+            }
+        }
+    };
+
+    // 先写入执行记录再结束日志：执行中的日志在检查错过的执行时视为已开始的执行，
+    // 两者之间不留空档，补执行不会在这一刻重复启动同一任务
+    let added = db.add_history(&history);
+    if let Err(e) = finish_log(db, &log_store, &log_path) {
+        eprintln!("结束任务 {} 的日志失败: {}", job.id, e);
+    }
+    added.map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+    // 通知在后台发送
+    notify::dispatch(job, &history);
+
+    Ok(history)
+}
+
+/// 结束本次执行的日志，读取日志设置失败时按默认设置处理，保证日志不会一直处于执行中
+fn finish_log(db: &Database, log_store: &LogStore, log_path: &Path) -> Result<(), Error> {
+    let settings: logs::LogSettings = match db.get_setting(logs::SETTINGS_KEY) {
+        Ok(settings) => settings.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("读取日志设置失败: {}", e);
+            logs::LogSettings::default()
+        }
+    };
+    log_store.finish_run_log(log_path, &settings)
+}
+
+/// 执行任务，并按依赖关系依次触发条件满足的下游任务（下游任务完成后继续触发其下游）。
+/// 返回的第一条为任务本身的执行记录
+pub fn run_chain(db: &Database, job: &CronJob, trigger: RunTrigger) -> Result<Vec<JobHistory>, Error> {
+    let history = run_job(db, job, trigger)?;
+    let mut queue = VecDeque::from([(job.id.clone(), history.status.clone())]);
+    let mut histories = vec![history];
+    // 保存时已做环检测，这里再去重一次，防止数据库被外部修改后无限触发
+    let mut visited = HashSet::from([job.id.clone()]);
+
+    while let Some((upstream_id, status)) = queue.pop_front() {
+        let condition = match status {
+            JobStatus::Success => TriggerCondition::OnSuccess,
+            JobStatus::Failed => TriggerCondition::OnFailure,
+        };
+        let downstream_jobs = db.get_downstream_jobs(&upstream_id, &condition)
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        for downstream in downstream_jobs {
+            if !downstream.is_active || !visited.insert(downstream.id.clone()) {
+                continue;
+            }
+            let history = run_job(db, &downstream, RunTrigger::Dependency)?;
+            queue.push_back((downstream.id.clone(), history.status.clone()));
+            histories.push(history);
+        }
+    }
+
+    Ok(histories)
+}
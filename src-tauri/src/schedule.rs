@@ -0,0 +1,154 @@
+use chrono::{DateTime, Duration, Local, Utc};
+use cron::Schedule;
+use std::str::FromStr;
+use crate::Error;
+
+/// crontab 星期字段的数字取值（0 和 7 都表示周日）
+const WEEKDAYS: [&str; 8] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+
+/// 将 crontab 的星期字段转换为星期名称。
+/// cron 库的星期序号从 1（周日）开始，与 crontab 的 0（周日）不一致，直接使用数字会错位一天
+fn translate_weekdays(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+    let mut number = String::new();
+    // 步长（`/` 之后）的数字不是星期，保持原样
+    let mut in_step = false;
+
+    fn flush(number: &mut String, result: &mut String, in_step: bool) {
+        if number.is_empty() {
+            return;
+        }
+        match number.parse::<usize>() {
+            Ok(day) if !in_step && day < WEEKDAYS.len() => result.push_str(WEEKDAYS[day]),
+            _ => result.push_str(number),
+        }
+        number.clear();
+    }
+
+    for c in field.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        flush(&mut number, &mut result, in_step);
+        match c {
+            '/' => in_step = true,
+            ',' => in_step = false,
+            _ => {}
+        }
+        result.push(c);
+    }
+    flush(&mut number, &mut result, in_step);
+
+    result
+}
+
+/// 解析 5 字段的 crontab 表达式或 `@daily` 等简写。
+/// `@reboot` 与空表达式（只由上游任务触发）没有固定的执行时间，返回 `None`
+pub fn parse_schedule(expression: &str) -> Result<Option<Schedule>, Error> {
+    let expression = expression.trim();
+
+    let normalized = match expression {
+        "" | "@reboot" => return Ok(None),
+        "@annually" => "@yearly".to_string(),
+        "@midnight" => "@daily".to_string(),
+        _ if expression.starts_with('@') => expression.to_string(),
+        _ => {
+            let parts: Vec<&str> = expression.split_whitespace().collect();
+            if parts.len() != 5 {
+                return Err(Error::InvalidCronExpression(format!(
+                    "'{}' 必须包含5个字段：分钟 小时 日期 月份 星期",
+                    expression
+                )));
+            }
+            // 补上秒字段
+            format!("0 {} {} {} {} {}", parts[0], parts[1], parts[2], parts[3], translate_weekdays(parts[4]))
+        }
+    };
+
+    Schedule::from_str(&normalized)
+        .map(Some)
+        .map_err(|e| Error::InvalidCronExpression(format!("'{}': {}", expression, e)))
+}
+
+/// 单次最多计算的计划执行次数，避免 `* * * * *` 配合很长的时间窗口占用过多时间与内存
+pub const MAX_FIRE_TIMES: usize = 100_000;
+
+/// 按时间顺序惰性计算 `[from, to)` 区间内的计划执行时间，按本地时区解释表达式，
+/// 最多返回 `MAX_FIRE_TIMES` 个。`@reboot` 与空表达式没有计划执行时间
+pub fn fire_times_between(
+    expression: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<impl Iterator<Item = DateTime<Utc>>, Error> {
+    let schedule = parse_schedule(expression)?;
+
+    // `after` 只返回严格晚于起点的时间，从前一秒开始计算才能包含恰好在 `from` 的执行
+    let start = (from - Duration::seconds(1)).with_timezone(&Local);
+    Ok(schedule
+        .into_iter()
+        .flat_map(move |schedule| schedule.after_owned(start))
+        .map(|time| time.with_timezone(&Utc))
+        .skip_while(move |time| *time < from)
+        .take_while(move |time| *time < to)
+        .take(MAX_FIRE_TIMES))
+}
+
+/// `[from, to)` 区间内的计划执行次数，达到 `MAX_FIRE_TIMES` 时无法给出准确次数，返回 `None`
+pub fn count_fire_times(expression: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Option<u64>, Error> {
+    let count = fire_times_between(expression, from, to)?.count();
+    Ok((count < MAX_FIRE_TIMES).then_some(count as u64))
+}
+
+/// 从现在开始的 `count` 次计划执行时间，没有计划执行时间的表达式返回空列表
+pub fn next_fire_times(expression: &str, count: usize) -> Result<Vec<DateTime<Local>>, Error> {
+    Ok(parse_schedule(expression)?
+        .map(|schedule| schedule.upcoming(Local).take(count).collect())
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn window_includes_start_and_excludes_end() {
+        let times: Vec<_> = fire_times_between("*/15 * * * *", time("2024-01-01T00:00:00Z"), time("2024-01-01T01:00:00Z"))
+            .unwrap()
+            .collect();
+        assert_eq!(times, [
+            time("2024-01-01T00:00:00Z"),
+            time("2024-01-01T00:15:00Z"),
+            time("2024-01-01T00:30:00Z"),
+            time("2024-01-01T00:45:00Z"),
+        ]);
+    }
+
+    #[test]
+    fn count_is_capped() {
+        let from = time("2000-01-01T00:00:00Z");
+        assert_eq!(count_fire_times("*/30 * * * *", from, from + Duration::days(1)).unwrap(), Some(48));
+        assert_eq!(count_fire_times("* * * * *", from, from + Duration::days(365)).unwrap(), None);
+    }
+
+    #[test]
+    fn schedules_without_fire_times() {
+        let from = time("2024-01-01T00:00:00Z");
+        assert_eq!(fire_times_between("@reboot", from, from + Duration::days(1)).unwrap().count(), 0);
+        assert!(next_fire_times("", 5).unwrap().is_empty());
+        assert_eq!(next_fire_times("@hourly", 3).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn weekday_numbers_follow_crontab() {
+        assert_eq!(translate_weekdays("0"), "SUN");
+        assert_eq!(translate_weekdays("1-5"), "MON-FRI");
+        assert_eq!(translate_weekdays("*/2"), "*/2");
+        assert_eq!(translate_weekdays("0,7"), "SUN,SUN");
+        assert!(parse_schedule("0 9 * *").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::db::Database;
+use crate::{crontab, deps, CronJob, Error};
+
+/// 导出文档的格式版本，导入时拒绝更高版本的文档
+pub const DOCUMENT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ExportFormat {
+    /// 根据文件扩展名判断格式
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// 导出的任务文档
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobDocument {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub jobs: Vec<CronJob>,
+}
+
+/// 导入的任务 ID 与现有任务冲突时的处理方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum ConflictStrategy {
+    /// 保留现有任务，不导入
+    #[default]
+    Skip,
+    /// 用导入的任务覆盖现有任务
+    Overwrite,
+    /// 以新的 ID 导入
+    Rename,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub enum ImportAction {
+    Create,
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+/// 导入报告中的一项，`job_id` 为导入后的任务 ID
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportItem {
+    pub source_id: String,
+    pub job_id: String,
+    pub name: Option<String>,
+    pub action: ImportAction,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub items: Vec<ImportItem>,
+}
+
+impl ImportReport {
+    /// 实际写入数据库的任务 ID
+    pub fn imported_ids(&self) -> Vec<&str> {
+        self.items
+            .iter()
+            .filter(|item| item.action != ImportAction::Skip && item.error.is_none())
+            .map(|item| item.job_id.as_str())
+            .collect()
+    }
+}
+
+/// 将任务导出为指定格式的文档
+pub fn export_jobs(jobs: &[CronJob], format: ExportFormat) -> Result<String, Error> {
+    let document = JobDocument {
+        version: DOCUMENT_VERSION,
+        exported_at: Utc::now(),
+        jobs: jobs.to_vec(),
+    };
+
+    let content = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&document).map_err(|e| e.to_string()),
+        ExportFormat::Yaml => serde_yaml::to_string(&document).map_err(|e| e.to_string()),
+        ExportFormat::Toml => toml::to_string_pretty(&document).map_err(|e| e.to_string()),
+    };
+    content.map_err(|e| Error::InvalidImport(format!("导出失败: {}", e)))
+}
+
+/// 解析导出文档并检查版本
+pub fn parse_document(content: &str, format: ExportFormat) -> Result<JobDocument, Error> {
+    let document: JobDocument = match format {
+        ExportFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+        ExportFormat::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+        ExportFormat::Toml => toml::from_str(content).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| Error::InvalidImport(format!("文档格式错误: {}", e)))?;
+
+    if document.version > DOCUMENT_VERSION {
+        return Err(Error::InvalidImport(format!(
+            "不支持的文档版本 {}，当前支持的最高版本为 {}",
+            document.version, DOCUMENT_VERSION
+        )));
+    }
+
+    Ok(document)
+}
+
+/// 将 crontab 文件转换为导入文档，与读取系统 crontab 使用同一个解析器。
+/// 没有 `# JOB_ID:` 标记的任务行分配新的 ID；runner 调用只引用另一台机器数据库中的任务，无法导入，直接跳过。
+/// 导入的任务默认停用，确认后再启用
+pub fn document_from_crontab(content: &str) -> JobDocument {
+    let jobs = crontab::parse_all_entries(content)
+        .into_iter()
+        .filter_map(|entry| {
+            let command = entry.command()?.replace("\\%", "%");
+            let id = if entry.job_id.is_empty() {
+                Uuid::new_v4().to_string()
+            } else {
+                entry.job_id
+            };
+            Some(CronJob {
+                id,
+                name: entry.name,
+                schedule: entry.schedule,
+                command,
+                mailto: entry.mailto,
+                ..CronJob::default()
+            })
+        })
+        .collect();
+
+    JobDocument {
+        version: DOCUMENT_VERSION,
+        exported_at: Utc::now(),
+        jobs,
+    }
+}
+
+/// 导入文档中的任务。先按冲突处理方式生成导入计划并校验执行计划与依赖，
+/// 任一任务校验失败时不写入任何任务；`dry_run` 时只返回计划
+pub fn import_jobs(
+    db: &Database,
+    document: &JobDocument,
+    strategy: ConflictStrategy,
+    dry_run: bool,
+) -> Result<ImportReport, Error> {
+    let existing = db.get_all_jobs()
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+    let mut taken_ids: HashSet<String> = existing.iter().map(|job| job.id.clone()).collect();
+    let mut renamed: HashMap<String, String> = HashMap::new();
+    let mut items = Vec::new();
+    let mut planned = Vec::new();
+
+    for job in &document.jobs {
+        let action = match (taken_ids.contains(&job.id), strategy) {
+            (false, _) => ImportAction::Create,
+            (true, ConflictStrategy::Skip) => ImportAction::Skip,
+            (true, ConflictStrategy::Overwrite) => ImportAction::Overwrite,
+            (true, ConflictStrategy::Rename) => ImportAction::Rename,
+        };
+
+        let mut job = job.clone();
+        let source_id = job.id.clone();
+        if action == ImportAction::Rename {
+            job.id = Uuid::new_v4().to_string();
+            renamed.insert(source_id.clone(), job.id.clone());
+        }
+        taken_ids.insert(job.id.clone());
+
+        items.push(ImportItem {
+            source_id,
+            job_id: job.id.clone(),
+            name: job.name.clone(),
+            action: action.clone(),
+            error: None,
+        });
+        if action != ImportAction::Skip {
+            planned.push(job);
+        }
+    }
+
+    // 依赖中的上游任务同样被改名时，指向新的 ID
+    for job in &mut planned {
+        for dependency in &mut job.depends_on {
+            if let Some(new_id) = renamed.get(&dependency.upstream_id) {
+                dependency.upstream_id = new_id.clone();
+            }
+        }
+    }
+
+    // 导入后的完整任务集合，用于校验依赖
+    let mut jobs: Vec<CronJob> = existing
+        .into_iter()
+        .filter(|job| !planned.iter().any(|planned| planned.id == job.id))
+        .collect();
+    jobs.extend(planned.iter().cloned());
+
+    for job in &planned {
+        let result = job.validate().and_then(|_| deps::validate_dependencies(&jobs, job));
+        if let Err(e) = result {
+            if let Some(item) = items.iter_mut().find(|item| item.job_id == job.id) {
+                item.error = Some(e.to_string());
+            }
+        }
+    }
+
+    let report = ImportReport { dry_run, items };
+    if dry_run {
+        return Ok(report);
+    }
+
+    let errors: Vec<String> = report.items
+        .iter()
+        .filter_map(|item| item.error.as_ref().map(|error| format!("{}: {}", item.source_id, error)))
+        .collect();
+    if !errors.is_empty() {
+        return Err(Error::InvalidImport(errors.join("; ")));
+    }
+
+    db.save_jobs(&planned)
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+    Ok(report)
+}